@@ -0,0 +1,234 @@
+use crate::components::{Falling, Position, Projectile, Stunned, Unit};
+use crate::events::{DamageSource, Event, Events};
+use crate::geometry::in_circle;
+use crate::systems::apply_damage;
+use hecs::{Entity, With, World};
+use serde::Serialize;
+
+const METEOR_DAMAGE: i32 = 60;
+// steps a meteor falls before hitting the ground
+pub const METEOR_FALL_STEPS: usize = 30;
+const FREEZE_STEPS: usize = 120;
+// steps during which kill bounties are doubled
+pub const GOLD_RUSH_STEPS: usize = 600;
+
+// Player cast abilities, targeted ones are aimed with the cursor
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum Ability {
+    // area damage
+    Meteor,
+    // area stun
+    Freeze,
+    // doubled kill bounties for a while
+    GoldRush,
+}
+
+impl Ability {
+    pub const ALL: [Ability; 3] = [Ability::Meteor, Ability::Freeze, Ability::GoldRush];
+
+    pub fn cost(self) -> i32 {
+        match self {
+            Ability::Meteor => 40,
+            Ability::Freeze => 20,
+            Ability::GoldRush => 30,
+        }
+    }
+
+    // simulation steps before the ability can be cast again
+    pub fn cooldown(self) -> usize {
+        match self {
+            Ability::Meteor => 300,
+            Ability::Freeze => 400,
+            Ability::GoldRush => 1200,
+        }
+    }
+
+    // area of effect, None for abilities that are not aimed
+    pub fn radius(self) -> Option<f32> {
+        match self {
+            Ability::Meteor => Some(80.0),
+            Ability::Freeze => Some(150.0),
+            Ability::GoldRush => None,
+        }
+    }
+
+    fn index(self) -> usize {
+        match self {
+            Ability::Meteor => 0,
+            Ability::Freeze => 1,
+            Ability::GoldRush => 2,
+        }
+    }
+}
+
+// Cooldowns of the abilities, counted in simulation steps so that they stop while paused
+#[derive(Default)]
+pub struct Abilities {
+    ready_at: [usize; 3],
+}
+
+impl Abilities {
+    // steps left before `ability` is ready
+    pub fn cooldown_left(&self, ability: Ability, step: usize) -> usize {
+        self.ready_at[ability.index()].saturating_sub(step)
+    }
+
+    pub fn is_ready(&self, ability: Ability, step: usize) -> bool {
+        self.cooldown_left(ability, step) == 0
+    }
+
+    pub fn start_cooldown(&mut self, ability: Ability, step: usize) {
+        self.ready_at[ability.index()] = step + ability.cooldown();
+    }
+}
+
+fn units_in_area(world: &World, center: &Position, radius: f32) -> Vec<Entity> {
+    world
+        .query::<With<Unit, &Position>>()
+        .iter()
+        .filter(|(_, position)| in_circle(position, center, radius))
+        .map(|(id, _)| id)
+        .collect()
+}
+
+// Starts the ability, cooldown and cost are checked by the caller. Meteors fall as
+// projectiles and hit when they land, the other abilities take effect right away
+pub fn cast(world: &mut World, ability: Ability, position: Option<Position>, events: &mut Events) {
+    match (ability, &position) {
+        (Ability::Meteor, Some(center)) => {
+            world.spawn((
+                Projectile,
+                center.clone(),
+                ability,
+                Falling {
+                    steps: METEOR_FALL_STEPS,
+                },
+            ));
+        }
+        (_, Some(center)) => impact(world, ability, center, events),
+        _ => {}
+    }
+    events.push(Event::AbilityCast { ability, position });
+}
+
+// area effect of the ability around `center`
+fn impact(world: &mut World, ability: Ability, center: &Position, events: &mut Events) {
+    match (ability, ability.radius()) {
+        (Ability::Meteor, Some(radius)) => {
+            for unit in units_in_area(world, center, radius) {
+                apply_damage(
+                    world,
+                    unit,
+                    METEOR_DAMAGE,
+                    DamageSource::Ability(ability),
+                    events,
+                );
+            }
+        }
+        (Ability::Freeze, Some(radius)) => {
+            for unit in units_in_area(world, center, radius) {
+                let stunned = Stunned {
+                    steps: FREEZE_STEPS,
+                };
+                world.insert_one(unit, stunned).ok();
+            }
+        }
+        _ => {}
+    }
+}
+
+// Falling projectiles land once their fall is over and apply their ability there
+pub fn system_projectile_impacts(world: &mut World, events: &mut Events) {
+    let mut landed: Vec<(Entity, Position, Ability)> = Vec::new();
+    for (id, (position, ability, falling)) in
+        world.query_mut::<With<Projectile, (&Position, &Ability, &mut Falling)>>()
+    {
+        falling.steps = falling.steps.saturating_sub(1);
+        if falling.steps == 0 {
+            landed.push((id, position.clone(), *ability));
+        }
+    }
+    for (entity, position, ability) in landed {
+        world.despawn(entity).unwrap();
+        impact(world, ability, &position, events);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::Health;
+
+    fn unit(world: &mut World, x: f32, health: i32) -> Entity {
+        let health = Health {
+            value: health,
+            max: health,
+        };
+        world.spawn((Unit, Position { x, y: 0.0 }, health))
+    }
+
+    #[test]
+    fn abilities_start_ready_and_cool_down_independently() {
+        let mut abilities = Abilities::default();
+        assert!(Ability::ALL.iter().all(|a| abilities.is_ready(*a, 0)));
+        abilities.start_cooldown(Ability::Meteor, 10);
+        assert_eq!(abilities.cooldown_left(Ability::Meteor, 10), 300);
+        assert_eq!(abilities.cooldown_left(Ability::Meteor, 300), 10);
+        assert!(abilities.is_ready(Ability::Meteor, 310));
+        assert!(abilities.is_ready(Ability::Freeze, 10));
+    }
+
+    #[test]
+    fn freeze_stuns_units_in_its_radius() {
+        let mut world = World::new();
+        let mut events = Events::default();
+        let inside = unit(&mut world, 150.0, 10);
+        let outside = unit(&mut world, 151.0, 10);
+        let center = Position { x: 0.0, y: 0.0 };
+        cast(&mut world, Ability::Freeze, Some(center), &mut events);
+        assert_eq!(world.get::<Stunned>(inside).unwrap().steps, FREEZE_STEPS);
+        assert!(world.get::<Stunned>(outside).is_err());
+        let cast: Vec<Event> = events.drain().map(|(_, event)| event).collect();
+        assert!(matches!(
+            cast.as_slice(),
+            [Event::AbilityCast {
+                ability: Ability::Freeze,
+                position: Some(_)
+            }]
+        ));
+    }
+
+    #[test]
+    fn meteors_fall_then_damage_units_in_their_radius() {
+        let mut world = World::new();
+        let mut events = Events::default();
+        let killed = unit(&mut world, 10.0, METEOR_DAMAGE);
+        let hurt = unit(&mut world, 80.0, 100);
+        let missed = unit(&mut world, 81.0, 100);
+        let center = Position { x: 0.0, y: 0.0 };
+        cast(&mut world, Ability::Meteor, Some(center), &mut events);
+        // the meteor hits once it has fallen
+        for _ in 1..METEOR_FALL_STEPS {
+            system_projectile_impacts(&mut world, &mut events);
+        }
+        assert_eq!(world.get::<Health>(killed).unwrap().value, METEOR_DAMAGE);
+        assert_eq!(world.query::<&Projectile>().iter().count(), 1);
+        system_projectile_impacts(&mut world, &mut events);
+        assert_eq!(world.query::<&Projectile>().iter().count(), 0);
+        assert_eq!(world.get::<Health>(killed).unwrap().value, 0);
+        assert_eq!(
+            world.get::<Health>(hurt).unwrap().value,
+            100 - METEOR_DAMAGE
+        );
+        assert_eq!(world.get::<Health>(missed).unwrap().value, 100);
+        let meteor = DamageSource::Ability(Ability::Meteor);
+        let kills: Vec<Entity> = events
+            .drain()
+            .filter_map(|(_, event)| match event {
+                Event::UnitKilled { unit, source, .. } if source == meteor => Some(unit),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(kills, vec![killed]);
+    }
+}
@@ -1,73 +1,165 @@
-use macroquad::prelude::{
-    is_key_down, is_key_pressed, is_mouse_button_pressed, mouse_position, Camera2D, KeyCode,
-    MouseButton, Vec2,
-};
+use crate::abilities::Ability;
+use crate::bindings::KeyBindings;
+use crate::components::TowerKind;
+use macroquad::prelude::{mouse_position, mouse_wheel, Camera2D, Vec2};
+use macroquad::ui::root_ui;
+use serde::Serialize;
 
 pub(crate) enum Action {
     Build(Vec2),
     ChangeMode(Mode),
-    View(Vec2),
+    // start of a click or box selection in view mode
+    SelectStart(Vec2),
+    // end of the selection, extends the current selection when additive
+    SelectEnd(Vec2, bool),
+    SellSelected,
+    UpgradeSelected,
+    RepairSelected,
+    RebuildSelected,
+    CycleTargeting,
+    // picks the tower kind built in build mode
+    SelectShop(TowerKind),
+    // aims an ability with an area of effect
+    SelectAbility(Ability),
+    // casts the ability, at the given position for aimed ones
+    CastAbility(Ability, Option<Vec2>),
+    // simulation steps per frame
+    SetSpeed(usize),
     Remove(Vec2),
-    PrintState,
+    // orders the hero to walk to the position
+    MoveHero(Vec2),
+    Dump,
     Quit,
     Spawn,
     TogglePause,
     ToggleDebug,
+    ToggleHelp,
+    ToggleHealthBars,
+    ToggleDamageNumbers,
+    ToggleDeathEffects,
+    ToggleFollow,
 }
 
 pub(crate) enum CameraAction {
     Zoom(f32),
-    Target(f32, f32),
+    // zoom factor, screen position kept in place
+    ZoomAt(f32, Vec2),
+    Pan(f32, f32),
+    // screen position of the cursor while the drag button is held
+    Drag(Vec2),
+    FitMap,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
 pub(crate) enum Mode {
     Build,
     Remove,
     View,
+    // the cursor previews the area of the ability, clicking casts it
+    Aim(Ability),
+    // clicking moves the hero
+    Hero,
 }
 
-pub(crate) fn read_camera_action() -> Option<CameraAction> {
-    if is_key_down(KeyCode::Left) {
-        Some(CameraAction::Target(-1.0, 0.0))
-    } else if is_key_down(KeyCode::Right) {
-        Some(CameraAction::Target(1.0, 0.0))
-    } else if is_key_down(KeyCode::Up) {
-        Some(CameraAction::Target(0.0, -1.0))
-    } else if is_key_down(KeyCode::Down) {
-        Some(CameraAction::Target(0.0, 1.0))
-    } else if is_key_down(KeyCode::J) {
-        Some(CameraAction::Zoom(0.9))
-    } else if is_key_down(KeyCode::K) {
+// clicks and wheel events over the HUD are not forwarded to the world
+pub(crate) fn is_mouse_over_ui() -> bool {
+    root_ui().is_mouse_over(Vec2::from(mouse_position()))
+}
+
+pub(crate) fn read_camera_action(keys: &KeyBindings) -> Option<CameraAction> {
+    let (_wheel_x, wheel_y) = mouse_wheel();
+    let over_ui = is_mouse_over_ui();
+    if keys.drag.is_down() {
+        Some(CameraAction::Drag(Vec2::from(mouse_position())))
+    } else if wheel_y != 0.0 && !over_ui {
+        // wheel magnitude differs between platforms, only its sign is used
+        let factor = if wheel_y > 0.0 { 1.1 } else { 0.9 };
+        Some(CameraAction::ZoomAt(factor, Vec2::from(mouse_position())))
+    } else if keys.fit_map.is_pressed() {
+        Some(CameraAction::FitMap)
+    } else if keys.camera_left.is_down() {
+        Some(CameraAction::Pan(-1.0, 0.0))
+    } else if keys.camera_right.is_down() {
+        Some(CameraAction::Pan(1.0, 0.0))
+    } else if keys.camera_up.is_down() {
+        Some(CameraAction::Pan(0.0, -1.0))
+    } else if keys.camera_down.is_down() {
+        Some(CameraAction::Pan(0.0, 1.0))
+    } else if keys.zoom_in.is_down() {
         Some(CameraAction::Zoom(1.1))
+    } else if keys.zoom_out.is_down() {
+        Some(CameraAction::Zoom(0.9))
     } else {
         None
     }
 }
 
-pub(crate) fn read_simulation_action(camera: &Camera2D, mode: &Mode) -> Option<Action> {
-    if is_key_pressed(KeyCode::Space) {
+pub(crate) fn read_simulation_action(
+    keys: &KeyBindings,
+    camera: &Camera2D,
+    mode: &Mode,
+) -> Option<Action> {
+    if keys.pause.is_pressed() {
         Some(Action::TogglePause)
-    } else if is_key_pressed(KeyCode::D) {
+    } else if keys.remove_mode.is_pressed() {
         Some(Action::ChangeMode(Mode::Remove))
-    } else if is_key_pressed(KeyCode::I) {
+    } else if keys.build_mode.is_pressed() {
         Some(Action::ChangeMode(Mode::Build))
-    } else if is_key_pressed(KeyCode::P) {
-        Some(Action::PrintState)
-    } else if is_key_pressed(KeyCode::Q) {
+    } else if keys.dump.is_pressed() {
+        Some(Action::Dump)
+    } else if keys.quit.is_pressed() {
         Some(Action::Quit)
-    } else if is_key_pressed(KeyCode::R) {
+    } else if keys.spawn.is_pressed() {
         Some(Action::Spawn)
-    } else if is_key_pressed(KeyCode::V) {
+    } else if keys.view_mode.is_pressed() {
         Some(Action::ChangeMode(Mode::View))
-    } else if is_key_pressed(KeyCode::Equal) {
+    } else if keys.hero_mode.is_pressed() {
+        Some(Action::ChangeMode(Mode::Hero))
+    } else if keys.debug.is_pressed() {
         Some(Action::ToggleDebug)
-    } else if is_mouse_button_pressed(MouseButton::Left) {
+    } else if keys.help.is_pressed() {
+        Some(Action::ToggleHelp)
+    } else if keys.health_bars.is_pressed() {
+        Some(Action::ToggleHealthBars)
+    } else if keys.damage_numbers.is_pressed() {
+        Some(Action::ToggleDamageNumbers)
+    } else if keys.death_effects.is_pressed() {
+        Some(Action::ToggleDeathEffects)
+    } else if keys.follow.is_pressed() {
+        Some(Action::ToggleFollow)
+    } else if keys.sell.is_pressed() {
+        Some(Action::SellSelected)
+    } else if keys.upgrade.is_pressed() {
+        Some(Action::UpgradeSelected)
+    } else if keys.repair.is_pressed() {
+        Some(Action::RepairSelected)
+    } else if keys.rebuild.is_pressed() {
+        Some(Action::RebuildSelected)
+    } else if keys.targeting.is_pressed() {
+        Some(Action::CycleTargeting)
+    } else if keys.meteor.is_pressed() {
+        Some(Action::SelectAbility(Ability::Meteor))
+    } else if keys.freeze.is_pressed() {
+        Some(Action::SelectAbility(Ability::Freeze))
+    } else if keys.gold_rush.is_pressed() {
+        Some(Action::CastAbility(Ability::GoldRush, None))
+    } else if keys.select.is_pressed() && !is_mouse_over_ui() {
         let world_position = camera.screen_to_world(Vec2::from(mouse_position()));
         match mode {
             Mode::Build => Some(Action::Build(world_position)),
             Mode::Remove => Some(Action::Remove(world_position)),
-            Mode::View => Some(Action::View(world_position)),
+            Mode::View => Some(Action::SelectStart(world_position)),
+            Mode::Aim(ability) => Some(Action::CastAbility(*ability, Some(world_position))),
+            Mode::Hero => Some(Action::MoveHero(world_position)),
+        }
+    } else if keys.select.is_released() {
+        let world_position = camera.screen_to_world(Vec2::from(mouse_position()));
+        match mode {
+            Mode::View => Some(Action::SelectEnd(
+                world_position,
+                keys.add_to_selection.is_down(),
+            )),
+            _ => None,
         }
     } else {
         None
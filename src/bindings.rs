@@ -0,0 +1,356 @@
+use anyhow::bail;
+use macroquad::prelude::{
+    is_key_down, is_key_pressed, is_key_released, is_mouse_button_down, is_mouse_button_pressed,
+    is_mouse_button_released, KeyCode, MouseButton,
+};
+use serde::Deserialize;
+use std::convert::TryFrom;
+use std::fmt;
+
+// Key names are the macroquad `KeyCode` variant names e.g. "Space", "A", "Key1", "F5"
+// mouse buttons are "MouseLeft", "MouseRight" and "MouseMiddle"
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Hash)]
+#[serde(try_from = "String")]
+pub enum Input {
+    Key(KeyCode),
+    Mouse(MouseButton),
+}
+
+const MOUSE_BUTTONS: &[(&str, MouseButton)] = &[
+    ("MouseLeft", MouseButton::Left),
+    ("MouseRight", MouseButton::Right),
+    ("MouseMiddle", MouseButton::Middle),
+];
+
+const KEY_CODES: &[KeyCode] = &[
+    KeyCode::Space,
+    KeyCode::Apostrophe,
+    KeyCode::Comma,
+    KeyCode::Minus,
+    KeyCode::Period,
+    KeyCode::Slash,
+    KeyCode::Key0,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::Semicolon,
+    KeyCode::Equal,
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+    KeyCode::LeftBracket,
+    KeyCode::Backslash,
+    KeyCode::RightBracket,
+    KeyCode::GraveAccent,
+    KeyCode::Escape,
+    KeyCode::Enter,
+    KeyCode::Tab,
+    KeyCode::Backspace,
+    KeyCode::Insert,
+    KeyCode::Delete,
+    KeyCode::Right,
+    KeyCode::Left,
+    KeyCode::Down,
+    KeyCode::Up,
+    KeyCode::PageUp,
+    KeyCode::PageDown,
+    KeyCode::Home,
+    KeyCode::End,
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+    KeyCode::F12,
+    KeyCode::Kp0,
+    KeyCode::Kp1,
+    KeyCode::Kp2,
+    KeyCode::Kp3,
+    KeyCode::Kp4,
+    KeyCode::Kp5,
+    KeyCode::Kp6,
+    KeyCode::Kp7,
+    KeyCode::Kp8,
+    KeyCode::Kp9,
+    KeyCode::KpDecimal,
+    KeyCode::KpDivide,
+    KeyCode::KpMultiply,
+    KeyCode::KpSubtract,
+    KeyCode::KpAdd,
+    KeyCode::KpEnter,
+    KeyCode::KpEqual,
+    KeyCode::LeftShift,
+    KeyCode::LeftControl,
+    KeyCode::LeftAlt,
+    KeyCode::RightShift,
+    KeyCode::RightControl,
+    KeyCode::RightAlt,
+];
+
+impl Input {
+    pub fn is_down(&self) -> bool {
+        match self {
+            Input::Key(code) => is_key_down(*code),
+            Input::Mouse(button) => is_mouse_button_down(*button),
+        }
+    }
+
+    pub fn is_released(&self) -> bool {
+        match self {
+            Input::Key(code) => is_key_released(*code),
+            Input::Mouse(button) => is_mouse_button_released(*button),
+        }
+    }
+
+    pub fn is_pressed(&self) -> bool {
+        match self {
+            Input::Key(code) => is_key_pressed(*code),
+            Input::Mouse(button) => is_mouse_button_pressed(*button),
+        }
+    }
+}
+
+impl TryFrom<String> for Input {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        if let Some((_, button)) = MOUSE_BUTTONS.iter().find(|(n, _)| *n == name) {
+            return Ok(Input::Mouse(*button));
+        }
+        KEY_CODES
+            .iter()
+            .find(|code| format!("{:?}", code) == name)
+            .map(|code| Input::Key(*code))
+            .ok_or_else(|| format!("unknown key or mouse button `{}`", name))
+    }
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Input::Key(code) => write!(f, "{:?}", code),
+            Input::Mouse(button) => match MOUSE_BUTTONS.iter().find(|(_, b)| b == button) {
+                Some((name, _)) => write!(f, "{}", name),
+                None => write!(f, "{:?}", button),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeyBindings {
+    pub(crate) camera_left: Input,
+    pub(crate) camera_right: Input,
+    pub(crate) camera_up: Input,
+    pub(crate) camera_down: Input,
+    pub(crate) zoom_in: Input,
+    pub(crate) zoom_out: Input,
+    pub(crate) drag: Input,
+    pub(crate) fit_map: Input,
+    pub(crate) follow: Input,
+    pub(crate) pause: Input,
+    pub(crate) remove_mode: Input,
+    pub(crate) build_mode: Input,
+    pub(crate) view_mode: Input,
+    pub(crate) hero_mode: Input,
+    pub(crate) dump: Input,
+    pub(crate) quit: Input,
+    pub(crate) spawn: Input,
+    pub(crate) debug: Input,
+    pub(crate) select: Input,
+    pub(crate) add_to_selection: Input,
+    pub(crate) sell: Input,
+    pub(crate) upgrade: Input,
+    pub(crate) repair: Input,
+    pub(crate) rebuild: Input,
+    pub(crate) targeting: Input,
+    pub(crate) meteor: Input,
+    pub(crate) freeze: Input,
+    pub(crate) gold_rush: Input,
+    pub(crate) help: Input,
+    pub(crate) health_bars: Input,
+    pub(crate) damage_numbers: Input,
+    pub(crate) death_effects: Input,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        use Input::{Key, Mouse};
+        KeyBindings {
+            camera_left: Key(KeyCode::Left),
+            camera_right: Key(KeyCode::Right),
+            camera_up: Key(KeyCode::Up),
+            camera_down: Key(KeyCode::Down),
+            zoom_in: Key(KeyCode::K),
+            zoom_out: Key(KeyCode::J),
+            drag: Mouse(MouseButton::Right),
+            fit_map: Key(KeyCode::F),
+            follow: Key(KeyCode::T),
+            pause: Key(KeyCode::Space),
+            remove_mode: Key(KeyCode::D),
+            build_mode: Key(KeyCode::I),
+            view_mode: Key(KeyCode::V),
+            hero_mode: Key(KeyCode::O),
+            dump: Key(KeyCode::P),
+            quit: Key(KeyCode::Q),
+            spawn: Key(KeyCode::R),
+            debug: Key(KeyCode::Equal),
+            select: Mouse(MouseButton::Left),
+            add_to_selection: Key(KeyCode::LeftShift),
+            sell: Key(KeyCode::X),
+            upgrade: Key(KeyCode::U),
+            repair: Key(KeyCode::E),
+            rebuild: Key(KeyCode::G),
+            targeting: Key(KeyCode::C),
+            meteor: Key(KeyCode::Key1),
+            freeze: Key(KeyCode::Key2),
+            gold_rush: Key(KeyCode::Key3),
+            help: Key(KeyCode::H),
+            health_bars: Key(KeyCode::B),
+            damage_numbers: Key(KeyCode::N),
+            death_effects: Key(KeyCode::M),
+        }
+    }
+}
+
+impl KeyBindings {
+    // (config key, input) pairs, in help overlay order
+    pub fn table(&self) -> Vec<(&'static str, Input)> {
+        vec![
+            ("camera_left", self.camera_left),
+            ("camera_right", self.camera_right),
+            ("camera_up", self.camera_up),
+            ("camera_down", self.camera_down),
+            ("zoom_in", self.zoom_in),
+            ("zoom_out", self.zoom_out),
+            ("drag", self.drag),
+            ("fit_map", self.fit_map),
+            ("follow", self.follow),
+            ("pause", self.pause),
+            ("remove_mode", self.remove_mode),
+            ("build_mode", self.build_mode),
+            ("view_mode", self.view_mode),
+            ("hero_mode", self.hero_mode),
+            ("dump", self.dump),
+            ("quit", self.quit),
+            ("spawn", self.spawn),
+            ("debug", self.debug),
+            ("select", self.select),
+            ("add_to_selection", self.add_to_selection),
+            ("sell", self.sell),
+            ("upgrade", self.upgrade),
+            ("repair", self.repair),
+            ("rebuild", self.rebuild),
+            ("targeting", self.targeting),
+            ("meteor", self.meteor),
+            ("freeze", self.freeze),
+            ("gold_rush", self.gold_rush),
+            ("help", self.help),
+            ("health_bars", self.health_bars),
+            ("damage_numbers", self.damage_numbers),
+            ("death_effects", self.death_effects),
+        ]
+    }
+
+    pub fn check_conflicts(&self) -> anyhow::Result<()> {
+        let table = self.table();
+        for (i, (name, input)) in table.iter().enumerate() {
+            if let Some((other, _)) = table[i + 1..].iter().find(|(_, other)| other == input) {
+                bail!(
+                    "keybindings.{} and keybindings.{} are both bound to `{}`",
+                    name,
+                    other,
+                    input
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inputs_parse_from_their_names() {
+        let parse = |name: &str| Input::try_from(name.to_string());
+        assert_eq!(parse("Space"), Ok(Input::Key(KeyCode::Space)));
+        assert_eq!(parse("Key1"), Ok(Input::Key(KeyCode::Key1)));
+        assert_eq!(parse("F5"), Ok(Input::Key(KeyCode::F5)));
+        assert_eq!(parse("MouseMiddle"), Ok(Input::Mouse(MouseButton::Middle)));
+        assert_eq!(
+            parse("space"),
+            Err("unknown key or mouse button `space`".to_string())
+        );
+        assert!(parse("").is_err());
+    }
+
+    #[test]
+    fn input_names_round_trip() {
+        for (_, input) in KeyBindings::default().table() {
+            assert_eq!(Input::try_from(input.to_string()), Ok(input));
+        }
+    }
+
+    #[test]
+    fn default_bindings_do_not_conflict() {
+        KeyBindings::default().check_conflicts().unwrap();
+    }
+
+    #[test]
+    fn check_conflicts_names_both_bindings() {
+        let bindings = KeyBindings {
+            help: Input::Key(KeyCode::Space),
+            ..KeyBindings::default()
+        };
+        assert_eq!(
+            bindings.check_conflicts().unwrap_err().to_string(),
+            "keybindings.pause and keybindings.help are both bound to `Space`"
+        );
+        let bindings = KeyBindings {
+            debug: Input::Mouse(MouseButton::Left),
+            ..KeyBindings::default()
+        };
+        assert_eq!(
+            bindings.check_conflicts().unwrap_err().to_string(),
+            "keybindings.debug and keybindings.select are both bound to `MouseLeft`"
+        );
+    }
+}
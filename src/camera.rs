@@ -0,0 +1,131 @@
+use crate::components::Position;
+use crate::config::DisplayConfig;
+use hecs::{Entity, World};
+use macroquad::prelude::{screen_height, screen_width, vec2, Camera2D, Vec2};
+
+// fraction of the half view width moved per frame by keyboard panning
+const KEYBOARD_PAN_SPEED: f32 = 0.02;
+// extra space around the map nodes when fitting the map
+const FIT_MARGIN: f32 = 1.1;
+// fraction of the remaining distance to the followed entity covered per frame
+const FOLLOW_SMOOTHING: f32 = 0.1;
+
+pub struct CameraController {
+    pub target: Vec2,
+    pub zoom: f32,
+    min_zoom: f32,
+    max_zoom: f32,
+    // world position held under the cursor while dragging
+    grab: Option<Vec2>,
+    following: Option<Entity>,
+}
+
+impl CameraController {
+    pub fn new(display: &DisplayConfig) -> Self {
+        CameraController {
+            target: vec2(0., 0.),
+            zoom: display.zoom.clamp(display.min_zoom, display.max_zoom),
+            min_zoom: display.min_zoom,
+            max_zoom: display.max_zoom,
+            grab: None,
+            following: None,
+        }
+    }
+
+    pub fn camera(&self) -> Camera2D {
+        Camera2D {
+            target: self.target,
+            zoom: vec2(self.zoom, -self.zoom * screen_width() / screen_height()),
+            ..Default::default()
+        }
+    }
+
+    // direction is in screen space, speed does not depend on zoom on screen
+    pub fn pan(&mut self, direction: Vec2) {
+        self.following = None;
+        self.target += direction * KEYBOARD_PAN_SPEED / self.zoom;
+    }
+
+    // keeps the world position under `screen_position` fixed
+    pub fn zoom_at(&mut self, factor: f32, screen_position: Vec2) {
+        if self.following.is_some() {
+            // keep the followed entity centered
+            self.zoom_at_center(factor);
+            return;
+        }
+        let before = self.camera().screen_to_world(screen_position);
+        self.zoom = (self.zoom * factor).clamp(self.min_zoom, self.max_zoom);
+        let after = self.camera().screen_to_world(screen_position);
+        self.target += before - after;
+    }
+
+    pub fn zoom_at_center(&mut self, factor: f32) {
+        self.zoom = (self.zoom * factor).clamp(self.min_zoom, self.max_zoom);
+    }
+
+    pub fn drag(&mut self, screen_position: Vec2) {
+        self.following = None;
+        match self.grab {
+            None => {
+                self.grab = Some(self.camera().screen_to_world(screen_position));
+            }
+            Some(grab) => {
+                let current = self.camera().screen_to_world(screen_position);
+                self.target += grab - current;
+            }
+        }
+    }
+
+    pub fn jump_to(&mut self, target: Vec2) {
+        self.following = None;
+        self.target = target;
+    }
+
+    pub fn release(&mut self) {
+        self.grab = None;
+    }
+
+    pub fn fit(&mut self, points: &[Position]) {
+        if points.is_empty() {
+            return;
+        }
+        self.following = None;
+        let min_x = points.iter().map(|p| p.x).fold(f32::MAX, f32::min);
+        let max_x = points.iter().map(|p| p.x).fold(f32::MIN, f32::max);
+        let min_y = points.iter().map(|p| p.y).fold(f32::MAX, f32::min);
+        let max_y = points.iter().map(|p| p.y).fold(f32::MIN, f32::max);
+        let width = (max_x - min_x).max(1.0) * FIT_MARGIN;
+        let height = (max_y - min_y).max(1.0) * FIT_MARGIN;
+        self.target = vec2((min_x + max_x) * 0.5, (min_y + max_y) * 0.5);
+        // the view spans 2 / zoom horizontally and 2 / (zoom * aspect) vertically
+        let aspect = screen_width() / screen_height();
+        let zoom = (2.0 / width).min(2.0 / (height * aspect));
+        self.zoom = zoom.clamp(self.min_zoom, self.max_zoom);
+    }
+
+    pub fn following(&self) -> Option<Entity> {
+        self.following
+    }
+
+    pub fn toggle_follow(&mut self, entity: Option<Entity>) {
+        self.following = match self.following {
+            Some(_) => None,
+            None => entity,
+        };
+    }
+
+    // moves the camera toward the followed entity, stops following once it is despawned
+    pub fn update_follow(&mut self, world: &World) {
+        if let Some(entity) = self.following {
+            match world.get::<Position>(entity) {
+                Ok(position) => {
+                    let position = vec2(position.x, position.y);
+                    self.target += (position - self.target) * FOLLOW_SMOOTHING;
+                }
+                Err(_) => {
+                    self.following = None;
+                }
+            }
+        }
+    }
+}
@@ -1,10 +1,14 @@
+use crate::geometry::distance_squared;
+use crate::map::NodeId;
 use crate::Vec2;
-use hecs::Entity;
+use hecs::{Entity, EntityRef};
+use serde::{Serialize, Serializer};
 use std::ops::{Add, Sub};
 
-pub type Distance = i32;
+// world units, fractional so that units move smoothly along any direction
+pub type Distance = f32;
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize)]
 pub struct Position {
     pub x: Distance,
     pub y: Distance,
@@ -12,10 +16,7 @@ pub struct Position {
 
 impl From<Vec2> for Position {
     fn from(v: Vec2) -> Self {
-        Position {
-            x: v.x as i32,
-            y: v.y as i32,
-        }
+        Position { x: v.x, y: v.y }
     }
 }
 
@@ -67,40 +68,251 @@ impl Position {
     pub fn norm_squared(&self) -> Distance {
         self.x * self.x + self.y * self.y
     }
+
+    pub fn norm(&self) -> Distance {
+        self.norm_squared().sqrt()
+    }
 }
 
 // i32 is convenient for inflicting damages > health
 // checks cane be done using health >= 0
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Health {
     pub value: i32,
     pub max: i32,
 }
 
 // distance / simulation step
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Speed(pub Distance);
 
 // Raw damage
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Damage(pub i32);
 
 // distance <= range => unit is at range
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Range {
     pub squared: Distance,
 }
 
-#[derive(Clone, Debug)]
+impl Range {
+    pub fn contains(&self, from: &Position, to: &Position) -> bool {
+        distance_squared(from, to) <= self.squared
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
 pub struct Score(pub i32);
 
-#[derive(Debug)]
+// How a tower picks its target among the units in range
+// First and Last compare the path distance left to the end
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub enum Targeting {
+    #[default]
+    Closest,
+    Strongest,
+    Weakest,
+    First,
+    Last,
+}
+
+impl Targeting {
+    pub fn next(self) -> Self {
+        match self {
+            Targeting::Closest => Targeting::Strongest,
+            Targeting::Strongest => Targeting::Weakest,
+            Targeting::Weakest => Targeting::First,
+            Targeting::First => Targeting::Last,
+            Targeting::Last => Targeting::Closest,
+        }
+    }
+}
+
+// Marks enemy units, towers also have health and some units deal damage
+#[derive(Clone, Debug, Serialize)]
+pub struct Unit;
+
+// Marks player towers
+#[derive(Clone, Debug, Serialize)]
+pub struct Tower;
+
+// Marks short lived entities travelling toward an impact point, such as meteors
+#[derive(Clone, Debug, Serialize)]
+pub struct Projectile;
+
+// Marks the player controlled hero, it fights units like a tower and is attacked like one
+#[derive(Clone, Debug, Serialize)]
+pub struct Hero;
+
+// What is left of a destroyed tower, it can be rebuilt in place
+#[derive(Clone, Debug, Serialize)]
+pub struct Ruin {
+    pub kind: TowerKind,
+}
+
+// What an entity is, derived from its marker component. Systems, rendering, selection
+// and dumps rely on the markers rather than on which other components are present
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum EntityKind {
+    Unit,
+    Tower,
+    Ruin,
+    Hero,
+    Projectile,
+}
+
+impl EntityKind {
+    pub fn of(entity: EntityRef<'_>) -> Option<Self> {
+        if entity.has::<Unit>() {
+            Some(EntityKind::Unit)
+        } else if entity.has::<Tower>() {
+            Some(EntityKind::Tower)
+        } else if entity.has::<Ruin>() {
+            Some(EntityKind::Ruin)
+        } else if entity.has::<Hero>() {
+            Some(EntityKind::Hero)
+        } else if entity.has::<Projectile>() {
+            Some(EntityKind::Projectile)
+        } else {
+            None
+        }
+    }
+
+    // projectiles are too short lived to be picked
+    pub fn is_selectable(self) -> bool {
+        self != EntityKind::Projectile
+    }
+}
+
+// How a unit moves, units without it walk along the lanes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub enum Movement {
+    #[default]
+    Ground,
+    // heads straight from its spawn to the closest goal
+    Flying,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum TowerKind {
+    Laser,
+    Sniper,
+    Cannon,
+    AntiAir,
+}
+
+impl TowerKind {
+    pub const ALL: [TowerKind; 4] = [
+        TowerKind::Laser,
+        TowerKind::Sniper,
+        TowerKind::Cannon,
+        TowerKind::AntiAir,
+    ];
+
+    pub fn cost(self) -> i32 {
+        match self {
+            TowerKind::Laser => 50,
+            TowerKind::Sniper => 80,
+            TowerKind::Cannon => 150,
+            TowerKind::AntiAir => 70,
+        }
+    }
+
+    pub fn max_health(self) -> i32 {
+        match self {
+            TowerKind::Laser => 100,
+            TowerKind::Sniper => 60,
+            TowerKind::Cannon => 200,
+            TowerKind::AntiAir => 80,
+        }
+    }
+
+    // cannons cannot hit flying units, anti air towers only hit them
+    pub fn can_target(self, movement: Movement) -> bool {
+        match self {
+            TowerKind::Laser | TowerKind::Sniper => true,
+            TowerKind::Cannon => movement == Movement::Ground,
+            TowerKind::AntiAir => movement == Movement::Flying,
+        }
+    }
+}
+
+// Gold invested in a tower (build and upgrades), half of it is refunded on sale
+#[derive(Clone, Debug, Serialize)]
+pub struct Value(pub i32);
+
+// Tower upgrade or hero level, starts at 1
+#[derive(Clone, Debug, Serialize)]
+pub struct Level(pub u32);
+
+// Cumulative combat statistics of a tower
+// overkill is the part of the damage exceeding the remaining health of its target
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct CombatStats {
+    pub damage_dealt: i32,
+    pub overkill: i32,
+    pub shots_fired: usize,
+    pub kills: usize,
+    pub steps_with_target: usize,
+}
+
+#[derive(Clone, Debug, Serialize)]
 pub struct Target {
     pub(crate) position: Option<Position>,
+    #[serde(serialize_with = "serialize_entity")]
     pub(crate) entity: Option<Entity>,
 }
 
-#[derive(Debug)]
+// entities are serialized as their raw bits, generation included
+pub fn serialize_entity<S: Serializer>(
+    entity: &Option<Entity>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match entity {
+        Some(entity) => serializer.serialize_some(&entity.to_bits().get()),
+        None => serializer.serialize_none(),
+    }
+}
+
+// Map node the unit is heading to
+#[derive(Clone, Debug, Serialize)]
 pub struct Waypoint {
-    pub index: usize,
+    pub node: NodeId,
+}
+
+// Distance covered since spawn and distance left along the shortest lane to a goal,
+// updated by the motion system. Forks ahead are not chosen yet, so `remaining` is the
+// shortest distance the unit can still leak in
+#[derive(Clone, Debug, Serialize)]
+pub struct PathProgress {
+    pub traveled: Distance,
+    pub remaining: Distance,
+}
+
+impl PathProgress {
+    // whole steps needed to reach the end at `speed` along the shortest lane, a lower bound
+    pub fn eta(&self, speed: &Speed) -> Option<usize> {
+        if speed.0 > 0.0 {
+            Some((self.remaining / speed.0).ceil() as usize)
+        } else {
+            None
+        }
+    }
+}
+
+// Frozen units neither move nor attack for the given number of steps
+#[derive(Clone, Debug, Serialize)]
+pub struct Stunned {
+    pub steps: usize,
+}
+
+// Position the hero was ordered to walk to, removed on arrival
+#[derive(Clone, Debug, Serialize)]
+pub struct Destination(pub Position);
+
+// Steps left before a falling projectile hits the ground
+#[derive(Clone, Debug, Serialize)]
+pub struct Falling {
+    pub steps: usize,
 }
@@ -1,19 +1,143 @@
-use clap::{App, Arg};
+use crate::bindings::KeyBindings;
+use anyhow::{bail, Context};
+use clap::{App, Arg, ArgMatches};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
+// used when no --config is given, silently skipped if missing
+const DEFAULT_CONFIG_PATH: &str = "td-rs.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
-    pub(crate) paused: bool,
+    pub(crate) gameplay: GameplayConfig,
+    pub(crate) display: DisplayConfig,
+    pub(crate) simulation: SimulationConfig,
+    pub(crate) output: OutputConfig,
+    pub(crate) keybindings: KeyBindings,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameplayConfig {
     pub(crate) towers: usize,
     pub(crate) units: usize,
+    // starting gold
+    pub(crate) gold: i32,
+    pub(crate) lives: i32,
 }
 
-impl Default for Config {
+impl Default for GameplayConfig {
     fn default() -> Self {
-        Config {
-            paused: false,
+        GameplayConfig {
             towers: 10,
             units: 10,
+            gold: 200,
+            lives: 20,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DisplayConfig {
+    pub(crate) zoom: f32,
+    pub(crate) min_zoom: f32,
+    pub(crate) max_zoom: f32,
+    pub(crate) debug: bool,
+    pub(crate) health_bars: bool,
+    pub(crate) damage_numbers: bool,
+    pub(crate) death_effects: bool,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        DisplayConfig {
+            zoom: 0.001,
+            min_zoom: 0.0001,
+            max_zoom: 0.05,
+            debug: false,
+            health_bars: true,
+            damage_numbers: true,
+            death_effects: true,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SimulationConfig {
+    pub(crate) paused: bool,
+    // simulation steps run per rendered frame
+    pub(crate) steps_per_frame: usize,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        SimulationConfig {
+            paused: false,
+            steps_per_frame: 1,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    pub(crate) report_json: Option<PathBuf>,
+    pub(crate) report_csv: Option<PathBuf>,
+    pub(crate) dump_dir: PathBuf,
+    pub(crate) dump_at_steps: Vec<usize>,
+    pub(crate) dump_on_exit: bool,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        OutputConfig {
+            report_json: None,
+            report_csv: None,
+            dump_dir: PathBuf::from("."),
+            dump_at_steps: vec![],
+            dump_on_exit: false,
+        }
+    }
+}
+
+impl Config {
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.display.min_zoom.is_nan() || self.display.min_zoom <= 0.0 {
+            bail!(
+                "display.min_zoom must be > 0, got {}",
+                self.display.min_zoom
+            );
+        }
+        if self.display.max_zoom.is_nan() || self.display.max_zoom < self.display.min_zoom {
+            bail!(
+                "display.max_zoom must be >= display.min_zoom, got {}",
+                self.display.max_zoom
+            );
+        }
+        if self.display.zoom.is_nan()
+            || self.display.zoom < self.display.min_zoom
+            || self.display.zoom > self.display.max_zoom
+        {
+            bail!(
+                "display.zoom must be within [{}, {}], got {}",
+                self.display.min_zoom,
+                self.display.max_zoom,
+                self.display.zoom
+            );
+        }
+        if self.gameplay.lives <= 0 {
+            bail!("gameplay.lives must be >= 1, got {}", self.gameplay.lives);
         }
+        if self.simulation.steps_per_frame == 0 {
+            bail!("simulation.steps_per_frame must be >= 1");
+        }
+        self.keybindings.check_conflicts()?;
+        Ok(())
     }
 }
 
@@ -22,6 +146,13 @@ pub fn get_app() -> App<'static, 'static> {
         .version(crate_version!())
         .author(crate_authors!())
         .about("Does awesome things")
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("TOML configuration file, defaults to ./td-rs.toml when present"),
+        )
         .arg(
             Arg::with_name("paused")
                 .long("paused")
@@ -29,34 +160,194 @@ pub fn get_app() -> App<'static, 'static> {
                 .required(false)
                 .help("start with simulation paused"),
         )
+        .arg(Arg::with_name("towers").long("towers").takes_value(true))
+        .arg(Arg::with_name("units").long("units").takes_value(true))
         .arg(
-            Arg::with_name("towers")
-                .long("towers")
+            Arg::with_name("report-json")
+                .long("report-json")
                 .takes_value(true)
-                .default_value("10"),
+                .value_name("FILE")
+                .help("write the end-of-run tower report as JSON"),
         )
         .arg(
-            Arg::with_name("units")
-                .long("units")
+            Arg::with_name("report-csv")
+                .long("report-csv")
                 .takes_value(true)
-                .default_value("10"),
+                .value_name("FILE")
+                .help("write the end-of-run tower report as CSV"),
         )
+        .arg(
+            Arg::with_name("dump-dir")
+                .long("dump-dir")
+                .takes_value(true)
+                .value_name("DIR")
+                .help("directory where world dumps are written"),
+        )
+        .arg(
+            Arg::with_name("dump-at-step")
+                .long("dump-at-step")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("STEP")
+                .help("dump the world when the simulation reaches STEP"),
+        )
+        .arg(
+            Arg::with_name("dump-on-exit")
+                .long("dump-on-exit")
+                .takes_value(false)
+                .help("dump the world when quitting"),
+        )
+}
+
+fn read_config_file(path: &Path) -> anyhow::Result<Config> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("failed to read config file {}", path.display()))?;
+    toml::from_str(&content).with_context(|| format!("invalid config file {}", path.display()))
+}
+
+fn apply_overrides(config: &mut Config, matches: &ArgMatches) -> anyhow::Result<()> {
+    if matches.is_present("paused") {
+        config.simulation.paused = true;
+    }
+    if let Some(t) = matches.value_of("towers") {
+        config.gameplay.towers = usize::from_str(t).context("invalid --towers")?;
+    }
+    if let Some(u) = matches.value_of("units") {
+        config.gameplay.units = usize::from_str(u).context("invalid --units")?;
+    }
+    if let Some(path) = matches.value_of("report-json") {
+        config.output.report_json = Some(PathBuf::from(path));
+    }
+    if let Some(path) = matches.value_of("report-csv") {
+        config.output.report_csv = Some(PathBuf::from(path));
+    }
+    if let Some(d) = matches.value_of("dump-dir") {
+        config.output.dump_dir = PathBuf::from(d);
+    }
+    if let Some(steps) = matches.values_of("dump-at-step") {
+        config.output.dump_at_steps = steps
+            .map(usize::from_str)
+            .collect::<Result<Vec<usize>, _>>()
+            .context("invalid --dump-at-step")?;
+    }
+    if matches.is_present("dump-on-exit") {
+        config.output.dump_on_exit = true;
+    }
+    Ok(())
 }
 
+// file values override defaults, command line flags override file values
 pub fn get_config() -> anyhow::Result<Config> {
     let matches = get_app().get_matches();
-    let paused = matches.is_present("paused");
-    let towers = match matches.value_of("towers") {
-        Some(t) => usize::from_str(t)?,
-        None => Config::default().towers,
-    };
-    let units = match matches.value_of("units") {
-        Some(u) => usize::from_str(u)?,
-        None => Config::default().units,
+    let mut config = match matches.value_of("config") {
+        Some(path) => read_config_file(Path::new(path))?,
+        None => {
+            let default_path = Path::new(DEFAULT_CONFIG_PATH);
+            if default_path.exists() {
+                read_config_file(default_path)?
+            } else {
+                Config::default()
+            }
+        }
     };
-    Ok(Config {
-        paused,
-        towers,
-        units,
-    })
+    apply_overrides(&mut config, &matches)?;
+    config.validate()?;
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::Input;
+    use macroquad::prelude::{KeyCode, MouseButton};
+
+    fn parse(content: &str) -> anyhow::Result<Config> {
+        let config: Config = toml::from_str(content)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn error(content: &str) -> String {
+        parse(content).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        Config::default().validate().unwrap();
+        let config = parse("").unwrap();
+        assert_eq!(config.gameplay.units, 10);
+        assert_eq!(config.gameplay.gold, 200);
+        assert_eq!(config.simulation.steps_per_frame, 1);
+    }
+
+    #[test]
+    fn file_values_override_defaults() {
+        let config = parse(
+            "[gameplay]\nunits = 3\n\n[keybindings]\npause = \"MouseMiddle\"\nhelp = \"F1\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.gameplay.units, 3);
+        assert_eq!(config.gameplay.towers, 10);
+        assert_eq!(config.keybindings.pause, Input::Mouse(MouseButton::Middle));
+        assert_eq!(config.keybindings.help, Input::Key(KeyCode::F1));
+        assert_eq!(config.keybindings.quit, Input::Key(KeyCode::Q));
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        assert!(parse("[gameplay]\nunit = 3\n").is_err());
+        assert!(parse("[sound]\nvolume = 1\n").is_err());
+        assert!(parse("[keybindings]\njump = \"Space\"\n").is_err());
+        assert!(parse("[keybindings]\npause = \"Spacebar\"\n").is_err());
+    }
+
+    #[test]
+    fn validate_rejects_out_of_range_values() {
+        assert_eq!(
+            error("[display]\nmin_zoom = 0.0\n"),
+            "display.min_zoom must be > 0, got 0"
+        );
+        assert_eq!(
+            error("[display]\nmin_zoom = 0.01\nmax_zoom = 0.001\n"),
+            "display.max_zoom must be >= display.min_zoom, got 0.001"
+        );
+        assert_eq!(
+            error("[display]\nzoom = 1.0\n"),
+            "display.zoom must be within [0.0001, 0.05], got 1"
+        );
+        assert_eq!(
+            error("[gameplay]\nlives = 0\n"),
+            "gameplay.lives must be >= 1, got 0"
+        );
+        assert_eq!(
+            error("[simulation]\nsteps_per_frame = 0\n"),
+            "simulation.steps_per_frame must be >= 1"
+        );
+        assert_eq!(
+            error("[keybindings]\nhelp = \"Q\"\n"),
+            "keybindings.quit and keybindings.help are both bound to `Q`"
+        );
+    }
+
+    #[test]
+    fn command_line_flags_override_file_values() {
+        let mut config = parse("[gameplay]\nunits = 3\n").unwrap();
+        let matches = get_app().get_matches_from(vec![
+            "td-rs",
+            "--units",
+            "7",
+            "--paused",
+            "--dump-at-step",
+            "10",
+            "--dump-at-step",
+            "20",
+        ]);
+        apply_overrides(&mut config, &matches).unwrap();
+        assert_eq!(config.gameplay.units, 7);
+        assert!(config.simulation.paused);
+        assert_eq!(config.output.dump_at_steps, vec![10, 20]);
+        let matches = get_app().get_matches_from(vec!["td-rs", "--towers", "many"]);
+        assert!(apply_overrides(&mut config, &matches).is_err());
+    }
 }
@@ -0,0 +1,284 @@
+use crate::abilities::Ability;
+use crate::actions::Mode;
+use crate::components::{
+    CombatStats, Damage, Destination, EntityKind, Falling, Health, Level, Movement, PathProgress,
+    Position, Range, Ruin, Score, Speed, Stunned, Target, Targeting, TowerKind, Value, Waypoint,
+};
+use crate::map::Map;
+use hecs::{EntityRef, World};
+use serde::Serialize;
+use std::fs::File;
+use std::io::BufWriter;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+// Global (non ECS) state included in dumps
+#[derive(Serialize)]
+pub struct GlobalState<'a> {
+    pub step: usize,
+    pub wave: usize,
+    pub arrived: usize,
+    pub gold: i32,
+    pub lives: i32,
+    pub paused: bool,
+    pub mode: &'a Mode,
+    pub hero_level: u32,
+    pub hero_experience: u32,
+    // step at which the dead hero respawns
+    pub hero_respawn_at: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct EntityDump {
+    id: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kind: Option<EntityKind>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    position: Option<Position>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    health: Option<Health>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    speed: Option<Speed>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    waypoint: Option<Waypoint>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    progress: Option<PathProgress>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    movement: Option<Movement>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    damage: Option<Damage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    range: Option<Range>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    score: Option<Score>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target: Option<Target>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    combat_stats: Option<CombatStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    targeting: Option<Targeting>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    level: Option<Level>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tower_kind: Option<TowerKind>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ruin: Option<Ruin>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stunned: Option<Stunned>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    destination: Option<Destination>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ability: Option<Ability>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    falling: Option<Falling>,
+}
+
+impl From<EntityRef<'_>> for EntityDump {
+    fn from(entity: EntityRef<'_>) -> Self {
+        EntityDump {
+            id: entity.entity().to_bits().get(),
+            kind: EntityKind::of(entity),
+            position: entity.get::<Position>().map(|c| c.deref().clone()),
+            health: entity.get::<Health>().map(|c| c.deref().clone()),
+            speed: entity.get::<Speed>().map(|c| c.deref().clone()),
+            waypoint: entity.get::<Waypoint>().map(|c| c.deref().clone()),
+            progress: entity.get::<PathProgress>().map(|c| c.deref().clone()),
+            movement: entity.get::<Movement>().map(|c| *c),
+            damage: entity.get::<Damage>().map(|c| c.deref().clone()),
+            range: entity.get::<Range>().map(|c| c.deref().clone()),
+            score: entity.get::<Score>().map(|c| c.deref().clone()),
+            target: entity.get::<Target>().map(|c| c.deref().clone()),
+            combat_stats: entity.get::<CombatStats>().map(|c| c.deref().clone()),
+            targeting: entity.get::<Targeting>().map(|c| *c),
+            level: entity.get::<Level>().map(|c| c.deref().clone()),
+            tower_kind: entity.get::<TowerKind>().map(|c| *c),
+            value: entity.get::<Value>().map(|c| c.deref().clone()),
+            ruin: entity.get::<Ruin>().map(|c| c.deref().clone()),
+            stunned: entity.get::<Stunned>().map(|c| c.deref().clone()),
+            destination: entity.get::<Destination>().map(|c| c.deref().clone()),
+            ability: entity.get::<Ability>().map(|c| *c),
+            falling: entity.get::<Falling>().map(|c| c.deref().clone()),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct WorldDump<'a> {
+    global: GlobalState<'a>,
+    map: &'a Map,
+    entities: Vec<EntityDump>,
+    // ids of the units from the closest to the end to the farthest
+    units_by_progress: Vec<u64>,
+}
+
+// further dumps of the same step get a numbered suffix instead of overwriting the first one
+fn dump_path(directory: &Path, step: usize) -> PathBuf {
+    let mut path = directory.join(format!("world-step-{}.json", step));
+    let mut count = 1;
+    while path.exists() {
+        path = directory.join(format!("world-step-{}-{}.json", step, count));
+        count += 1;
+    }
+    path
+}
+
+// Dumps the world into `directory`, a failed dump is logged and the run goes on
+pub fn write_dump(world: &World, map: &Map, global: GlobalState, directory: &Path) {
+    let path = dump_path(directory, global.step);
+    if let Err(e) = dump_world(world, map, global, &path) {
+        error!("failed to dump world to {}: {}", path.display(), e);
+    }
+}
+
+// entities are sorted by id so that dumps of similar runs can be diffed
+fn dump_world(world: &World, map: &Map, global: GlobalState, path: &Path) -> anyhow::Result<()> {
+    let mut entities: Vec<EntityDump> = world.iter().map(EntityDump::from).collect();
+    entities.sort_by_key(|entity| entity.id);
+    let mut units: Vec<(u64, f32)> = entities
+        .iter()
+        .filter_map(|entity| entity.progress.as_ref().map(|p| (entity.id, p.remaining)))
+        .collect();
+    units.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+    let dump = WorldDump {
+        global,
+        map,
+        entities,
+        units_by_progress: units.into_iter().map(|(id, _)| id).collect(),
+    };
+    let writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(writer, &dump)?;
+    info!("world dumped to {}", path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::Unit;
+    use crate::map::{GoalZone, Node};
+    use serde_json::{json, Value};
+    use std::fs;
+
+    // dump of `world` on a map made of a single goal node
+    fn dump(world: &World, mode: &Mode, name: &str) -> Value {
+        let goal = Node {
+            position: Position { x: 5.0, y: 6.0 },
+            branches: vec![],
+        };
+        let zone = GoalZone {
+            center: Position { x: 5.0, y: 6.0 },
+            radius: 1.0,
+        };
+        let map = Map::new(vec![goal], vec![0], vec![zone]).unwrap();
+        let global = GlobalState {
+            step: 42,
+            wave: 3,
+            arrived: 1,
+            gold: 120,
+            lives: 9,
+            paused: true,
+            mode,
+            hero_level: 2,
+            hero_experience: 4,
+            hero_respawn_at: None,
+        };
+        let file = format!("td-rs-{}-{}.json", name, std::process::id());
+        let path = std::env::temp_dir().join(file);
+        dump_world(world, &map, global, &path).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        serde_json::from_str(&content).unwrap()
+    }
+
+    #[test]
+    fn dumps_global_state_the_map_and_every_component() {
+        let mut world = World::new();
+        let health = Health { value: 7, max: 10 };
+        let unit = world.spawn((Unit, Position { x: 1.0, y: 2.0 }, health, Speed(3.0)));
+        let tower = world.spawn((
+            Position { x: 0.0, y: 0.0 },
+            Damage(4),
+            CombatStats::default(),
+        ));
+        let dump = dump(&world, &Mode::Build, "dump");
+
+        assert_eq!(
+            dump["global"],
+            json!({
+                "step": 42,
+                "wave": 3,
+                "arrived": 1,
+                "gold": 120,
+                "lives": 9,
+                "paused": true,
+                "mode": "Build",
+                "hero_level": 2,
+                "hero_experience": 4,
+                "hero_respawn_at": null
+            })
+        );
+        assert_eq!(
+            dump["map"],
+            json!({
+                "nodes": [{"position": {"x": 5.0, "y": 6.0}, "branches": []}],
+                "spawns": [0],
+                "goal_zones": [{"center": {"x": 5.0, "y": 6.0}, "radius": 1.0}]
+            })
+        );
+        let entities = dump["entities"].as_array().unwrap();
+        assert_eq!(entities.len(), 2);
+        assert_eq!(entities[0]["id"], json!(unit.to_bits().get()));
+        assert_eq!(entities[0]["kind"], json!("Unit"));
+        assert_eq!(entities[0]["health"], json!({"value": 7, "max": 10}));
+        assert_eq!(entities[0]["speed"], json!(3.0));
+        // absent components are left out
+        assert!(entities[0].get("damage").is_none());
+        assert!(entities[1].get("kind").is_none());
+        assert_eq!(entities[1]["id"], json!(tower.to_bits().get()));
+        assert_eq!(entities[1]["damage"], json!(4));
+        assert_eq!(entities[1]["combat_stats"]["shots_fired"], json!(0));
+    }
+
+    #[test]
+    fn entities_are_sorted_by_id() {
+        let mut world = World::new();
+        let first = world.spawn((Speed(1.0),));
+        let second = world.spawn((Damage(1),));
+        world.despawn(first).unwrap();
+        let third = world.spawn((Speed(2.0),));
+        let dump = dump(&world, &Mode::View, "sorted");
+        let ids: Vec<u64> = dump["entities"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["id"].as_u64().unwrap())
+            .collect();
+        let mut expected = vec![second.to_bits().get(), third.to_bits().get()];
+        expected.sort();
+        assert_eq!(ids, expected);
+    }
+
+    #[test]
+    fn dumps_of_the_same_step_are_not_overwritten() {
+        let name = format!("td-rs-dumps-{}", std::process::id());
+        let directory = std::env::temp_dir().join(name);
+        fs::create_dir_all(&directory).unwrap();
+        let first = dump_path(&directory, 7);
+        assert_eq!(first, directory.join("world-step-7.json"));
+        fs::write(&first, "{}").unwrap();
+        let second = dump_path(&directory, 7);
+        assert_eq!(second, directory.join("world-step-7-1.json"));
+        fs::write(&second, "{}").unwrap();
+        assert_eq!(
+            dump_path(&directory, 7),
+            directory.join("world-step-7-2.json")
+        );
+        assert_eq!(
+            dump_path(&directory, 8),
+            directory.join("world-step-8.json")
+        );
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,145 @@
+use crate::abilities::{Ability, GOLD_RUSH_STEPS};
+use crate::config::GameplayConfig;
+use crate::events::Event;
+
+// gold earned per unit killed
+const KILL_BOUNTY: i32 = 5;
+
+// Player resources, income and lives lost are driven by gameplay events
+pub struct Economy {
+    pub gold: i32,
+    pub lives: i32,
+    // step until which kill bounties are doubled by a gold rush
+    gold_rush_until: usize,
+}
+
+impl Economy {
+    pub fn new(gameplay: &GameplayConfig) -> Self {
+        Economy {
+            gold: gameplay.gold,
+            lives: gameplay.lives,
+            gold_rush_until: 0,
+        }
+    }
+
+    pub fn try_spend(&mut self, cost: i32) -> bool {
+        if cost <= self.gold {
+            self.gold -= cost;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        self.lives <= 0
+    }
+
+    // steps left in the current gold rush
+    pub fn gold_rush_left(&self, step: usize) -> usize {
+        self.gold_rush_until.saturating_sub(step)
+    }
+
+    pub fn on_event(&mut self, event: &Event, step: usize) {
+        match event {
+            Event::UnitKilled { .. } => {
+                if step < self.gold_rush_until {
+                    self.gold += 2 * KILL_BOUNTY;
+                } else {
+                    self.gold += KILL_BOUNTY;
+                }
+            }
+            Event::AbilityCast {
+                ability: Ability::GoldRush,
+                ..
+            } => {
+                self.gold_rush_until = step + GOLD_RUSH_STEPS;
+            }
+            Event::UnitLeaked { .. } => {
+                self.lives -= 1;
+            }
+            Event::TowerSold { refund, .. } => {
+                self.gold += refund;
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::{CombatStats, Position};
+    use crate::events::tests::unit_killed;
+    use crate::events::DamageSource;
+    use hecs::World;
+
+    fn economy() -> Economy {
+        Economy::new(&GameplayConfig {
+            gold: 100,
+            lives: 2,
+            ..GameplayConfig::default()
+        })
+    }
+
+    fn cast(ability: Ability) -> Event {
+        Event::AbilityCast {
+            ability,
+            position: None,
+        }
+    }
+
+    #[test]
+    fn kills_pay_a_bounty_doubled_during_a_gold_rush() {
+        let mut economy = economy();
+        let kill = unit_killed(DamageSource::Ability(Ability::Meteor));
+        economy.on_event(&kill, 0);
+        assert_eq!(economy.gold, 100 + KILL_BOUNTY);
+        economy.on_event(&cast(Ability::GoldRush), 10);
+        assert_eq!(economy.gold_rush_left(10), GOLD_RUSH_STEPS);
+        economy.on_event(&kill, 10 + GOLD_RUSH_STEPS - 1);
+        assert_eq!(economy.gold, 100 + 3 * KILL_BOUNTY);
+        economy.on_event(&kill, 10 + GOLD_RUSH_STEPS);
+        assert_eq!(economy.gold, 100 + 4 * KILL_BOUNTY);
+        assert_eq!(economy.gold_rush_left(10 + GOLD_RUSH_STEPS), 0);
+    }
+
+    #[test]
+    fn other_abilities_do_not_start_a_gold_rush() {
+        let mut economy = economy();
+        economy.on_event(&cast(Ability::Meteor), 0);
+        economy.on_event(&cast(Ability::Freeze), 0);
+        assert_eq!(economy.gold_rush_left(0), 0);
+    }
+
+    #[test]
+    fn leaks_cost_lives_until_game_over() {
+        let mut world = World::new();
+        let mut economy = economy();
+        let leak = Event::UnitLeaked {
+            unit: world.spawn(()),
+        };
+        economy.on_event(&leak, 0);
+        assert_eq!(economy.lives, 1);
+        assert!(!economy.is_game_over());
+        economy.on_event(&leak, 0);
+        assert!(economy.is_game_over());
+    }
+
+    #[test]
+    fn spending_needs_enough_gold_and_sales_refund() {
+        let mut world = World::new();
+        let mut economy = economy();
+        assert!(!economy.try_spend(101));
+        assert!(economy.try_spend(100));
+        assert_eq!(economy.gold, 0);
+        let sold = Event::TowerSold {
+            tower: world.spawn(()),
+            position: Position { x: 0.0, y: 0.0 },
+            refund: 25,
+            stats: CombatStats::default(),
+        };
+        economy.on_event(&sold, 0);
+        assert_eq!(economy.gold, 25);
+    }
+}
@@ -0,0 +1,139 @@
+use crate::components::Position;
+use crate::events::Event;
+use macroquad::prelude::{draw_text, vec2, Camera2D, Color};
+use macroquad::shapes::draw_circle_lines;
+
+// lifetimes are in rendered frames so that effects fade even while paused
+const DAMAGE_NUMBER_FRAMES: u32 = 40;
+const DEATH_EFFECT_FRAMES: u32 = 20;
+// oldest effects are dropped beyond these counts
+const MAX_DAMAGE_NUMBERS: usize = 200;
+const MAX_DEATH_EFFECTS: usize = 100;
+// screen pixels a damage number rises over its lifetime
+const DAMAGE_NUMBER_RISE: f32 = 30.0;
+const DEATH_EFFECT_RADIUS: f32 = 20.0;
+const DEATH_EFFECT_WIDTH: f32 = 2.0;
+
+struct DamageNumber {
+    position: Position,
+    damage: i32,
+    age: u32,
+}
+
+struct DeathEffect {
+    position: Position,
+    age: u32,
+}
+
+// Short lived visual feedback spawned from gameplay events
+#[derive(Default)]
+pub struct Effects {
+    pub damage_numbers: bool,
+    pub death_effects: bool,
+    numbers: Vec<DamageNumber>,
+    deaths: Vec<DeathEffect>,
+}
+
+impl Effects {
+    pub fn new(damage_numbers: bool, death_effects: bool) -> Self {
+        Effects {
+            damage_numbers,
+            death_effects,
+            ..Default::default()
+        }
+    }
+
+    pub fn on_event(&mut self, event: &Event) {
+        match event {
+            Event::TowerFired {
+                damage, position, ..
+            }
+            | Event::TowerHit {
+                damage, position, ..
+            }
+            | Event::AbilityHit {
+                damage, position, ..
+            }
+            | Event::HeroFired {
+                damage, position, ..
+            }
+            | Event::HeroHit {
+                damage, position, ..
+            } if self.damage_numbers => {
+                if self.numbers.len() >= MAX_DAMAGE_NUMBERS {
+                    self.numbers.remove(0);
+                }
+                self.numbers.push(DamageNumber {
+                    position: position.clone(),
+                    damage: *damage,
+                    age: 0,
+                });
+            }
+            Event::UnitKilled { position, .. }
+            | Event::TowerDestroyed { position, .. }
+            | Event::HeroKilled { position, .. }
+                if self.death_effects =>
+            {
+                if self.deaths.len() >= MAX_DEATH_EFFECTS {
+                    self.deaths.remove(0);
+                }
+                self.deaths.push(DeathEffect {
+                    position: position.clone(),
+                    age: 0,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    pub fn toggle_damage_numbers(&mut self) {
+        self.damage_numbers = !self.damage_numbers;
+        self.numbers.clear();
+    }
+
+    pub fn toggle_death_effects(&mut self) {
+        self.death_effects = !self.death_effects;
+        self.deaths.clear();
+    }
+
+    // ages effects by one frame and forgets expired ones
+    pub fn update(&mut self) {
+        for number in self.numbers.iter_mut() {
+            number.age += 1;
+        }
+        self.numbers.retain(|n| n.age < DAMAGE_NUMBER_FRAMES);
+        for death in self.deaths.iter_mut() {
+            death.age += 1;
+        }
+        self.deaths.retain(|d| d.age < DEATH_EFFECT_FRAMES);
+    }
+
+    // world space, expanding fading rings
+    pub fn draw_deaths(&self) {
+        for death in &self.deaths {
+            let progress = death.age as f32 / DEATH_EFFECT_FRAMES as f32;
+            draw_circle_lines(
+                death.position.x,
+                death.position.y,
+                DEATH_EFFECT_RADIUS * (0.5 + progress),
+                DEATH_EFFECT_WIDTH,
+                Color::new(1.0, 0.0, 0.0, 1.0 - progress),
+            );
+        }
+    }
+
+    // screen space so that text is readable at any zoom
+    pub fn draw_damage_numbers(&self, camera: &Camera2D) {
+        for number in &self.numbers {
+            let progress = number.age as f32 / DAMAGE_NUMBER_FRAMES as f32;
+            let screen = camera.world_to_screen(vec2(number.position.x, number.position.y));
+            draw_text(
+                &number.damage.to_string(),
+                screen.x,
+                screen.y - DAMAGE_NUMBER_RISE * progress,
+                20.0,
+                Color::new(0.8, 0.0, 0.0, 1.0 - progress),
+            );
+        }
+    }
+}
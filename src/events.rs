@@ -0,0 +1,264 @@
+use crate::abilities::Ability;
+use crate::components::{CombatStats, Position};
+use hecs::Entity;
+use std::fmt;
+use std::vec::Drain;
+
+// What dealt the damage to a unit
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DamageSource {
+    Tower(Entity),
+    Ability(Ability),
+    Hero(Entity),
+}
+
+impl fmt::Display for DamageSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DamageSource::Tower(tower) => write!(f, "tower {:?}", tower),
+            DamageSource::Ability(ability) => write!(f, "{:?}", ability),
+            DamageSource::Hero(hero) => write!(f, "hero {:?}", hero),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Event {
+    UnitKilled {
+        unit: Entity,
+        source: DamageSource,
+        position: Position,
+    },
+    UnitLeaked {
+        unit: Entity,
+    },
+    TowerFired {
+        tower: Entity,
+        target: Entity,
+        damage: i32,
+        position: Position,
+    },
+    TowerBuilt {
+        tower: Entity,
+        position: Position,
+    },
+    // removed towers carry their final stats for the end of run report
+    TowerSold {
+        tower: Entity,
+        position: Position,
+        refund: i32,
+        stats: CombatStats,
+    },
+    WaveStarted {
+        wave: usize,
+        units: usize,
+    },
+    TowerHit {
+        tower: Entity,
+        unit: Entity,
+        damage: i32,
+        position: Position,
+    },
+    TowerDestroyed {
+        tower: Entity,
+        unit: Entity,
+        position: Position,
+        stats: CombatStats,
+    },
+    AbilityCast {
+        ability: Ability,
+        position: Option<Position>,
+    },
+    AbilityHit {
+        ability: Ability,
+        unit: Entity,
+        damage: i32,
+        position: Position,
+    },
+    HeroFired {
+        hero: Entity,
+        target: Entity,
+        damage: i32,
+        position: Position,
+    },
+    HeroHit {
+        hero: Entity,
+        unit: Entity,
+        damage: i32,
+        position: Position,
+    },
+    HeroKilled {
+        hero: Entity,
+        unit: Entity,
+        position: Position,
+    },
+    HeroLevelUp {
+        level: u32,
+    },
+    HeroRespawned {
+        hero: Entity,
+        position: Position,
+    },
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::UnitKilled {
+                unit,
+                source,
+                position,
+            } => write!(f, "unit {:?} killed by {} at {:?}", unit, source, position),
+            Event::UnitLeaked { unit } => write!(f, "unit {:?} leaked", unit),
+            Event::TowerFired {
+                tower,
+                target,
+                damage,
+                ..
+            } => write!(
+                f,
+                "tower {:?} fired at {:?} for {} HP",
+                tower, target, damage
+            ),
+            Event::TowerBuilt { tower, position } => {
+                write!(f, "tower {:?} built at {:?}", tower, position)
+            }
+            Event::TowerSold {
+                tower,
+                position,
+                refund,
+                ..
+            } => write!(
+                f,
+                "tower {:?} sold at {:?} for {} gold",
+                tower, position, refund
+            ),
+            Event::WaveStarted { wave, units } => {
+                write!(f, "wave {} started with {} units", wave, units)
+            }
+            Event::TowerHit {
+                tower,
+                unit,
+                damage,
+                ..
+            } => write!(
+                f,
+                "tower {:?} hit by unit {:?} for {} HP",
+                tower, unit, damage
+            ),
+            Event::TowerDestroyed {
+                tower,
+                unit,
+                position,
+                ..
+            } => write!(
+                f,
+                "tower {:?} destroyed by unit {:?} at {:?}",
+                tower, unit, position
+            ),
+            Event::AbilityCast { ability, position } => match position {
+                Some(position) => write!(f, "{:?} cast at {:?}", ability, position),
+                None => write!(f, "{:?} cast", ability),
+            },
+            Event::AbilityHit {
+                ability,
+                unit,
+                damage,
+                ..
+            } => write!(f, "{:?} hit unit {:?} for {} HP", ability, unit, damage),
+            Event::HeroFired {
+                hero,
+                target,
+                damage,
+                ..
+            } => write!(f, "hero {:?} hit {:?} for {} HP", hero, target, damage),
+            Event::HeroHit {
+                hero, unit, damage, ..
+            } => write!(
+                f,
+                "hero {:?} hit by unit {:?} for {} HP",
+                hero, unit, damage
+            ),
+            Event::HeroKilled {
+                hero,
+                unit,
+                position,
+            } => write!(
+                f,
+                "hero {:?} killed by unit {:?} at {:?}",
+                hero, unit, position
+            ),
+            Event::HeroLevelUp { level } => write!(f, "hero reached level {}", level),
+            Event::HeroRespawned { hero, position } => {
+                write!(f, "hero {:?} respawned at {:?}", hero, position)
+            }
+        }
+    }
+}
+
+// Systems push events while they run, consumers drain them once per frame
+// events are stamped with the simulation step they occurred at
+#[derive(Default)]
+pub struct Events {
+    step: usize,
+    queue: Vec<(usize, Event)>,
+}
+
+impl Events {
+    pub fn set_step(&mut self, step: usize) {
+        self.step = step;
+    }
+
+    pub fn push(&mut self, event: Event) {
+        self.queue.push((self.step, event));
+    }
+
+    pub fn drain(&mut self) -> Drain<'_, (usize, Event)> {
+        self.queue.drain(..)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use hecs::World;
+
+    // kill of some unit by `source`
+    pub(crate) fn unit_killed(source: DamageSource) -> Event {
+        Event::UnitKilled {
+            unit: Entity::DANGLING,
+            source,
+            position: Position { x: 0.0, y: 0.0 },
+        }
+    }
+
+    #[test]
+    fn events_are_stamped_with_their_step_and_drained_in_order() {
+        let mut world = World::new();
+        let unit = world.spawn(());
+        let mut events = Events::default();
+        events.push(Event::WaveStarted { wave: 1, units: 10 });
+        events.set_step(12);
+        events.push(Event::UnitLeaked { unit });
+        let drained: Vec<(usize, Event)> = events.drain().collect();
+        assert!(matches!(
+            drained.as_slice(),
+            [
+                (0, Event::WaveStarted { wave: 1, units: 10 }),
+                (12, Event::UnitLeaked { unit: leaked }),
+            ] if *leaked == unit
+        ));
+        assert_eq!(events.drain().count(), 0);
+    }
+
+    #[test]
+    fn events_describe_themselves() {
+        let event = Event::WaveStarted { wave: 2, units: 5 };
+        assert_eq!(event.to_string(), "wave 2 started with 5 units");
+        let cast = Event::AbilityCast {
+            ability: Ability::GoldRush,
+            position: None,
+        };
+        assert_eq!(cast.to_string(), "GoldRush cast");
+    }
+}
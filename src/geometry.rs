@@ -0,0 +1,184 @@
+use crate::components::{Distance, Position};
+use macroquad::prelude::{vec2, Vec2};
+
+// Distances are floats, squaring coordinates of any realistic map cannot overflow,
+// the helpers below are the only place computing them
+
+pub fn to_vec2(position: &Position) -> Vec2 {
+    vec2(position.x, position.y)
+}
+
+pub fn distance_squared(a: &Position, b: &Position) -> Distance {
+    (a - b).norm_squared()
+}
+
+pub fn distance(a: &Position, b: &Position) -> Distance {
+    (a - b).norm()
+}
+
+pub fn in_circle(point: &Position, center: &Position, radius: Distance) -> bool {
+    distance_squared(point, center) <= radius * radius
+}
+
+// distance from `point` to the closest point of segment [a, b]
+pub fn segment_distance(point: &Position, a: &Position, b: &Position) -> Distance {
+    let ab = b - a;
+    let length_squared = ab.norm_squared();
+    if length_squared == 0.0 {
+        return distance(point, a);
+    }
+    let ap = point - a;
+    let t = ((ap.x * ab.x + ap.y * ab.y) / length_squared).clamp(0.0, 1.0);
+    let closest = Position {
+        x: a.x + ab.x * t,
+        y: a.y + ab.y * t,
+    };
+    distance(point, &closest)
+}
+
+// candidate closest to `target`, if any is strictly closer than `max_distance`
+pub fn closest<'a, T>(
+    candidates: impl Iterator<Item = (T, &'a Position)>,
+    target: &Position,
+    max_distance: Distance,
+) -> Option<T> {
+    candidates
+        .map(|(candidate, position)| (candidate, distance_squared(position, target)))
+        .filter(|(_, d)| *d < max_distance * max_distance)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(candidate, _)| candidate)
+}
+
+// Parameter interval [t0, t1] of the part of segment [a, b] lying inside the circle,
+// None when the segment does not cross the circle
+pub fn segment_circle_interval(a: Vec2, b: Vec2, center: Vec2, radius: f32) -> Option<(f32, f32)> {
+    let d = b - a;
+    let f = a - center;
+    let qa = d.dot(d);
+    let qb = 2.0 * f.dot(d);
+    let qc = f.dot(f) - radius * radius;
+    if qa == 0.0 {
+        // degenerate segment
+        return if qc <= 0.0 { Some((0.0, 0.0)) } else { None };
+    }
+    let discriminant = qb * qb - 4.0 * qa * qc;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    let t0 = ((-qb - root) / (2.0 * qa)).max(0.0);
+    let t1 = ((-qb + root) / (2.0 * qa)).min(1.0);
+    if t0 <= t1 {
+        Some((t0, t1))
+    } else {
+        None
+    }
+}
+
+// Sub-segments of the path lying inside the circle
+pub fn covered_segments<'a>(
+    path: impl Iterator<Item = (&'a Position, &'a Position)>,
+    center: Vec2,
+    radius: f32,
+) -> Vec<(Vec2, Vec2)> {
+    let center_position = Position::from(center);
+    path.filter(|(p0, p1)| segment_distance(&center_position, p0, p1) <= radius)
+        .filter_map(|(p0, p1)| {
+            let (a, b) = (to_vec2(p0), to_vec2(p1));
+            segment_circle_interval(a, b, center, radius)
+                .map(|(t0, t1)| (a + (b - a) * t0, a + (b - a) * t1))
+        })
+        .collect()
+}
+
+// Length of the path lying inside the circle, from its covered segments
+pub fn path_coverage(segments: &[(Vec2, Vec2)]) -> f32 {
+    segments.iter().map(|(a, b)| (*b - *a).length()).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: Distance, y: Distance) -> Position {
+        Position { x, y }
+    }
+
+    #[test]
+    fn in_circle_includes_the_boundary() {
+        let center = point(1.0, 1.0);
+        assert!(in_circle(&point(4.0, 5.0), &center, 5.0));
+        assert!(!in_circle(&point(4.0, 5.1), &center, 5.0));
+        assert!(in_circle(&center, &center, 0.0));
+    }
+
+    #[test]
+    fn segment_distance_clamps_to_the_end_points() {
+        let (a, b) = (point(0.0, 0.0), point(10.0, 0.0));
+        assert_eq!(segment_distance(&point(5.0, 3.0), &a, &b), 3.0);
+        assert_eq!(segment_distance(&point(-3.0, 4.0), &a, &b), 5.0);
+        assert_eq!(segment_distance(&point(13.0, -4.0), &a, &b), 5.0);
+        // degenerate segment
+        assert_eq!(segment_distance(&point(3.0, 4.0), &a, &a), 5.0);
+    }
+
+    #[test]
+    fn closest_picks_the_nearest_candidate_strictly_within_range() {
+        let positions = [point(10.0, 0.0), point(0.0, 4.0), point(-3.0, 0.0)];
+        let candidates = || positions.iter().enumerate();
+        let target = point(0.0, 0.0);
+        assert_eq!(closest(candidates(), &target, 100.0), Some(2));
+        assert_eq!(closest(candidates(), &target, 3.5), Some(2));
+        assert_eq!(closest(candidates(), &target, 3.0), None);
+        assert_eq!(
+            closest(std::iter::empty::<((), &Position)>(), &target, 100.0),
+            None
+        );
+    }
+
+    #[test]
+    fn segment_circle_interval_clips_to_the_segment() {
+        let center = vec2(5.0, 0.0);
+        assert_eq!(
+            segment_circle_interval(vec2(0.0, 0.0), vec2(10.0, 0.0), center, 2.5),
+            Some((0.25, 0.75))
+        );
+        assert_eq!(
+            segment_circle_interval(vec2(5.0, 0.0), vec2(15.0, 0.0), center, 2.5),
+            Some((0.0, 0.25))
+        );
+        assert_eq!(
+            segment_circle_interval(vec2(0.0, 5.0), vec2(10.0, 5.0), center, 2.5),
+            None
+        );
+        assert_eq!(
+            segment_circle_interval(vec2(0.0, 0.0), vec2(1.0, 0.0), center, 2.5),
+            None
+        );
+    }
+
+    #[test]
+    fn covered_segments_are_clipped_to_the_circle() {
+        let (a, b) = (point(-10.0, 0.0), point(10.0, 0.0));
+        let path = || std::iter::once((&a, &b));
+        assert_eq!(
+            covered_segments(path(), vec2(0.0, 0.0), 2.0),
+            vec![(vec2(-2.0, 0.0), vec2(2.0, 0.0))]
+        );
+        assert!(covered_segments(path(), vec2(0.0, 5.0), 2.0).is_empty());
+    }
+
+    #[test]
+    fn path_coverage_sums_the_covered_lengths() {
+        let nodes = [
+            Position { x: -10.0, y: 0.0 },
+            Position { x: 0.0, y: 0.0 },
+            Position { x: 0.0, y: 10.0 },
+        ];
+        let path = || nodes.windows(2).map(|pair| (&pair[0], &pair[1]));
+        let coverage = |center, radius| path_coverage(&covered_segments(path(), center, radius));
+        assert_eq!(coverage(vec2(0.0, 0.0), 2.0), 4.0);
+        assert_eq!(coverage(vec2(0.0, 0.0), 20.0), 20.0);
+        assert_eq!(coverage(vec2(50.0, 50.0), 2.0), 0.0);
+    }
+}
@@ -0,0 +1,247 @@
+use crate::components::{
+    Damage, Destination, Distance, Health, Hero, Level, Position, Range, Speed, Target,
+};
+use crate::events::{DamageSource, Event, Events};
+use crate::map::Map;
+use hecs::{Entity, World};
+
+// steps between the death of the hero and its respawn
+const RESPAWN_STEPS: usize = 300;
+const HERO_SPEED: Distance = 4.0;
+// kills needed to reach the next level, per current level
+const KILLS_PER_LEVEL: u32 = 5;
+
+// stats of the hero at `level`
+fn hero_stats(level: u32) -> (Health, Damage, Range) {
+    let health = 100 + 25 * (level as i32 - 1);
+    (
+        Health {
+            value: health,
+            max: health,
+        },
+        Damage(3 + level as i32),
+        Range {
+            squared: 10_000.0 + 1_000.0 * (level - 1) as f32,
+        },
+    )
+}
+
+fn spawn_hero(world: &mut World, position: Position, level: u32) -> Entity {
+    let (health, damage, range) = hero_stats(level);
+    let target = Target {
+        position: None,
+        entity: None,
+    };
+    world.spawn((
+        Hero,
+        position,
+        health,
+        damage,
+        range,
+        Speed(HERO_SPEED),
+        target,
+        Level(level),
+    ))
+}
+
+// Level and experience of the hero survive its deaths, the entity is spawned again at the
+// base once the respawn delay is over
+pub struct HeroState {
+    // last spawned hero, kept after its death so that kills it landed in its last step
+    // are still credited
+    entity: Entity,
+    base: Position,
+    level: u32,
+    experience: u32,
+    respawn_at: Option<usize>,
+    leveled_up: bool,
+}
+
+impl HeroState {
+    // the hero starts at the center of the first goal zone
+    pub fn new(world: &mut World, map: &Map) -> Self {
+        let base = map
+            .goal_zones()
+            .first()
+            .map_or(Position { x: 0.0, y: 0.0 }, |zone| zone.center.clone());
+        HeroState {
+            entity: spawn_hero(world, base.clone(), 1),
+            base,
+            level: 1,
+            experience: 0,
+            respawn_at: None,
+            leveled_up: false,
+        }
+    }
+
+    // None while the hero is dead
+    pub fn entity(&self) -> Option<Entity> {
+        match self.respawn_at {
+            Some(_) => None,
+            None => Some(self.entity),
+        }
+    }
+
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    pub fn experience(&self) -> u32 {
+        self.experience
+    }
+
+    pub fn respawn_at(&self) -> Option<usize> {
+        self.respawn_at
+    }
+
+    // steps left before the dead hero respawns
+    pub fn respawn_left(&self, step: usize) -> Option<usize> {
+        self.respawn_at.map(|at| at.saturating_sub(step))
+    }
+
+    pub fn on_event(&mut self, event: &Event, step: usize) {
+        match event {
+            Event::UnitKilled {
+                source: DamageSource::Hero(hero),
+                ..
+            } if *hero == self.entity => {
+                self.experience += 1;
+                if self.experience >= KILLS_PER_LEVEL * self.level {
+                    self.experience -= KILLS_PER_LEVEL * self.level;
+                    self.level += 1;
+                    self.leveled_up = true;
+                }
+            }
+            Event::HeroKilled { hero, .. } if *hero == self.entity => {
+                self.respawn_at = Some(step + RESPAWN_STEPS);
+            }
+            _ => {}
+        }
+    }
+
+    // applies level ups to the hero and respawns it when its delay is over
+    pub fn update(&mut self, world: &mut World, step: usize, events: &mut Events) {
+        if self.leveled_up {
+            self.leveled_up = false;
+            events.push(Event::HeroLevelUp { level: self.level });
+            if let Some(hero) = self.entity() {
+                // leveling up also heals the hero
+                let (health, damage, range) = hero_stats(self.level);
+                world
+                    .insert(hero, (health, damage, range, Level(self.level)))
+                    .ok();
+            }
+        }
+        if self.respawn_at.is_some_and(|at| step >= at) {
+            let hero = spawn_hero(world, self.base.clone(), self.level);
+            self.entity = hero;
+            self.respawn_at = None;
+            events.push(Event::HeroRespawned {
+                hero,
+                position: self.base.clone(),
+            });
+        }
+    }
+
+    pub fn order_move(&self, world: &mut World, destination: Position) {
+        if let Some(hero) = self.entity() {
+            world.insert_one(hero, Destination(destination)).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::tests::unit_killed;
+    use crate::map::default_map;
+
+    fn hero_killed(hero: Entity) -> Event {
+        Event::HeroKilled {
+            hero,
+            unit: Entity::DANGLING,
+            position: Position { x: 0.0, y: 0.0 },
+        }
+    }
+
+    #[test]
+    fn kills_level_up_the_hero() {
+        let mut world = World::new();
+        let mut events = Events::default();
+        let mut state = HeroState::new(&mut world, &default_map());
+        let hero = state.entity().unwrap();
+        let kill = unit_killed(DamageSource::Hero(hero));
+        for _ in 1..KILLS_PER_LEVEL {
+            state.on_event(&kill, 0);
+        }
+        assert_eq!(state.level(), 1);
+        state.on_event(&kill, 0);
+        assert_eq!(state.level(), 2);
+
+        // the new level is applied on the next update
+        assert_eq!(world.get::<Level>(hero).unwrap().0, 1);
+        state.update(&mut world, 0, &mut events);
+        assert_eq!(world.get::<Level>(hero).unwrap().0, 2);
+        assert_eq!(world.get::<Damage>(hero).unwrap().0, hero_stats(2).1 .0);
+        let level_ups = events
+            .drain()
+            .filter(|(_, event)| matches!(event, Event::HeroLevelUp { level: 2 }))
+            .count();
+        assert_eq!(level_ups, 1);
+
+        // the second level needs twice as many kills
+        for _ in 0..2 * KILLS_PER_LEVEL - 1 {
+            state.on_event(&kill, 0);
+        }
+        assert_eq!(state.level(), 2);
+        state.on_event(&kill, 0);
+        assert_eq!(state.level(), 3);
+    }
+
+    #[test]
+    fn kills_of_other_sources_are_not_credited() {
+        let mut world = World::new();
+        let mut state = HeroState::new(&mut world, &default_map());
+        let other = world.spawn(());
+        for _ in 0..KILLS_PER_LEVEL {
+            state.on_event(&unit_killed(DamageSource::Hero(other)), 0);
+            state.on_event(&unit_killed(DamageSource::Tower(other)), 0);
+        }
+        assert_eq!(state.level(), 1);
+    }
+
+    #[test]
+    fn the_hero_respawns_with_its_level() {
+        let mut world = World::new();
+        let mut events = Events::default();
+        let mut state = HeroState::new(&mut world, &default_map());
+        let hero = state.entity().unwrap();
+        for _ in 0..KILLS_PER_LEVEL {
+            state.on_event(&unit_killed(DamageSource::Hero(hero)), 10);
+        }
+        state.on_event(&hero_killed(hero), 10);
+        world.despawn(hero).unwrap();
+        assert_eq!(state.entity(), None);
+        assert_eq!(state.respawn_left(100), Some(10 + RESPAWN_STEPS - 100));
+
+        state.update(&mut world, 10 + RESPAWN_STEPS - 1, &mut events);
+        assert_eq!(state.entity(), None);
+        state.update(&mut world, 10 + RESPAWN_STEPS, &mut events);
+        let respawned = state.entity().unwrap();
+        assert_ne!(respawned, hero);
+        assert_eq!(world.get::<Level>(respawned).unwrap().0, 2);
+        assert_eq!(state.respawn_left(10 + RESPAWN_STEPS), None);
+    }
+
+    #[test]
+    fn kills_landed_in_the_last_step_are_credited() {
+        let mut world = World::new();
+        let mut state = HeroState::new(&mut world, &default_map());
+        let hero = state.entity().unwrap();
+        state.on_event(&hero_killed(hero), 10);
+        state.on_event(&unit_killed(DamageSource::Hero(hero)), 10);
+        assert_eq!(state.entity(), None);
+        assert_eq!(state.experience(), 1);
+        assert_eq!(state.respawn_at(), Some(10 + RESPAWN_STEPS));
+    }
+}
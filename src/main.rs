@@ -3,136 +3,343 @@ extern crate log;
 #[macro_use]
 extern crate clap;
 
+mod abilities;
 mod actions;
+mod bindings;
+mod camera;
 mod components;
 mod config;
+mod dump;
+mod economy;
+mod effects;
+mod events;
+mod geometry;
+mod hero;
+mod map;
+mod minimap;
+mod report;
 mod spawns;
 mod systems;
+mod ui;
 
-use crate::actions::{read_camera_action, read_simulation_action, Action, CameraAction, Mode};
+use crate::abilities::{Abilities, Ability};
+use crate::actions::{
+    is_mouse_over_ui, read_camera_action, read_simulation_action, Action, CameraAction, Mode,
+};
+use crate::bindings::KeyBindings;
+use crate::camera::CameraController;
+use crate::components::TowerKind;
 use crate::config::get_config;
-use crate::spawns::Selection;
+use crate::dump::GlobalState;
+use crate::economy::Economy;
+use crate::effects::Effects;
+use crate::events::{Event, Events};
+use crate::hero::HeroState;
+use crate::map::Map;
+use crate::minimap::Minimap;
+use crate::report::ReportTable;
+use crate::spawns::{Selection, SelectionStatus};
+use crate::ui::Hud;
 use hecs::*;
 use macroquad::prelude::{
-    clear_background, draw_line, draw_text, get_fps, next_frame, screen_height, screen_width,
-    set_camera, set_default_camera, vec2, Camera2D, Color, Vec2, BLACK, DARKGRAY, GREEN, RED,
+    clear_background, draw_line, draw_text, get_fps, mouse_position, next_frame, set_camera,
+    set_default_camera, vec2, Camera2D, Color, Vec2, BLACK, DARKGRAY, GREEN, ORANGE, PURPLE, RED,
     WHITE,
 };
-use macroquad::shapes::{draw_circle, draw_circle_lines, draw_rectangle};
+use macroquad::shapes::{
+    draw_circle, draw_circle_lines, draw_rectangle, draw_rectangle_lines, draw_triangle,
+};
 
 const TOWER_RADIUS: f32 = 10.0;
 const UNIT_RADIUS: f32 = 5.0;
+// distance between a flying unit and its shadow
+const FLYING_SHADOW_OFFSET: f32 = 8.0;
 const LASER_WIDTH: f32 = 2.0;
 const WAYPOINTS_WIDTH: f32 = 2.0;
+const NODE_RADIUS: f32 = 20.0;
 const RANGE_WIDTH: f32 = 2.0;
+const SELECTION_RADIUS: f32 = 12.0;
+const HEALTH_BAR_WIDTH: f32 = 12.0;
+const HEALTH_BAR_HEIGHT: f32 = 2.0;
+// distance between the unit center and its health bar
+const HEALTH_BAR_OFFSET: f32 = 8.0;
+// a selection shorter than this on screen (pixels) is a click, not a box
+const CLICK_TOLERANCE: f32 = 5.0;
+const COVERAGE_WIDTH: f32 = 4.0;
+const STUN_OUTLINE_RADIUS: f32 = 7.0;
+const HERO_RADIUS: f32 = 8.0;
+const METEOR_RADIUS: f32 = 6.0;
+// height a meteor falls from, in world units above its impact point
+const METEOR_HEIGHT: f32 = 150.0;
 
-fn print_world_state(world: &mut World) {
-    println!("\nEntity stats:");
-    for (id, (health, position)) in
-        &mut world.query::<(&components::Health, &components::Position)>()
-    {
-        println!("(unit) ID: {:?}, {:?} {:?}", id, health, position);
-    }
-    for (id, (position, damage, range, score, target)) in &mut world.query::<(
-        &components::Position,
-        &components::Damage,
-        &components::Range,
-        &components::Score,
-        &components::Target,
-    )>() {
-        println!(
-            "(tower) ID: {:?}, {:?} {:?} {:?} {:?} {:?}",
-            id, position, damage, range, score, target,
-        );
-    }
+fn draw_health_bar(position: &components::Position, health_ratio: f32) {
+    let x = position.x - HEALTH_BAR_WIDTH * 0.5;
+    // world y axis points down on screen, as screen coordinates do
+    let y = position.y - HEALTH_BAR_OFFSET - HEALTH_BAR_HEIGHT;
+    draw_rectangle(x, y, HEALTH_BAR_WIDTH, HEALTH_BAR_HEIGHT, RED);
+    draw_rectangle(
+        x,
+        y,
+        HEALTH_BAR_WIDTH * health_ratio,
+        HEALTH_BAR_HEIGHT,
+        GREEN,
+    );
 }
 
-fn draw_world(world: &World) {
-    for (_id, (health, position)) in world
-        .query::<(&components::Health, &components::Position)>()
+fn draw_world(world: &World, health_bars: bool) {
+    for (_id, (health, position, movement, stunned)) in world
+        .query::<With<
+            components::Unit,
+            (
+                &components::Health,
+                &components::Position,
+                Option<&components::Movement>,
+                Option<&components::Stunned>,
+            ),
+        >>()
         .iter()
     {
         let health_ratio = (health.value as f32 / health.max as f32).clamp(0f32, 1f32);
         let color = Color::new(health_ratio, 0.0, 1.0 - health_ratio, 1.0f32);
-        draw_circle(position.x as f32, position.y as f32, UNIT_RADIUS, color);
+        match movement {
+            Some(components::Movement::Flying) => {
+                // flying units are triangles casting a shadow below them
+                draw_circle(
+                    position.x,
+                    position.y + FLYING_SHADOW_OFFSET,
+                    UNIT_RADIUS * 0.6,
+                    Color::new(0.0, 0.0, 0.0, 0.3),
+                );
+                draw_triangle(
+                    vec2(position.x, position.y - UNIT_RADIUS),
+                    vec2(position.x - UNIT_RADIUS, position.y + UNIT_RADIUS),
+                    vec2(position.x + UNIT_RADIUS, position.y + UNIT_RADIUS),
+                    color,
+                );
+            }
+            _ => draw_circle(position.x, position.y, UNIT_RADIUS, color),
+        }
+        // frozen units are outlined in cyan
+        if stunned.is_some() {
+            draw_circle_lines(
+                position.x,
+                position.y,
+                STUN_OUTLINE_RADIUS,
+                RANGE_WIDTH,
+                Color::new(0.0, 0.8, 1.0, 1.0),
+            );
+        }
+        if health_bars {
+            draw_health_bar(position, health_ratio);
+        }
     }
-    for (_id, position) in world
-        .query::<With<components::Damage, &components::Position>>()
+    for (_id, (health, position)) in world
+        .query::<With<components::Tower, (&components::Health, &components::Position)>>()
         .iter()
     {
         draw_rectangle(
-            position.x as f32 - TOWER_RADIUS * 0.5,
-            position.y as f32 - TOWER_RADIUS * 0.5,
+            position.x - TOWER_RADIUS * 0.5,
+            position.y - TOWER_RADIUS * 0.5,
             TOWER_RADIUS,
             TOWER_RADIUS,
             GREEN,
         );
+        // only damaged towers show their health
+        if health_bars && health.value < health.max {
+            draw_health_bar(position, health.value as f32 / health.max as f32);
+        }
     }
-    for (_id, (target, position)) in world
-        .query::<(&components::Target, &components::Position)>()
+    for (_id, (health, position, destination)) in world
+        .query::<With<
+            components::Hero,
+            (
+                &components::Health,
+                &components::Position,
+                Option<&components::Destination>,
+            ),
+        >>()
+        .iter()
+    {
+        draw_circle(position.x, position.y, HERO_RADIUS, PURPLE);
+        if let Some(destination) = destination {
+            draw_circle_lines(
+                destination.0.x,
+                destination.0.y,
+                HERO_RADIUS * 0.5,
+                RANGE_WIDTH,
+                PURPLE,
+            );
+        }
+        if health_bars && health.value < health.max {
+            draw_health_bar(position, health.value as f32 / health.max as f32);
+        }
+    }
+    for (_id, (position, falling)) in world
+        .query::<With<components::Projectile, (&components::Position, &components::Falling)>>()
+        .iter()
+    {
+        // meteors fall toward their shadow on the impact point
+        let height = METEOR_HEIGHT * falling.steps as f32 / abilities::METEOR_FALL_STEPS as f32;
+        draw_circle(
+            position.x,
+            position.y,
+            METEOR_RADIUS,
+            Color::new(0.0, 0.0, 0.0, 0.3),
+        );
+        draw_circle(position.x, position.y - height, METEOR_RADIUS, ORANGE);
+    }
+    for (_id, position) in world
+        .query::<With<components::Ruin, &components::Position>>()
+        .iter()
+    {
+        draw_rectangle_lines(
+            position.x - TOWER_RADIUS * 0.5,
+            position.y - TOWER_RADIUS * 0.5,
+            TOWER_RADIUS,
+            TOWER_RADIUS,
+            RANGE_WIDTH,
+            DARKGRAY,
+        );
+    }
+    for (_id, (target, position, unit)) in world
+        .query::<(
+            &components::Target,
+            &components::Position,
+            Option<&components::Unit>,
+        )>()
         .iter()
     {
         if let Some(target_position) = &target.position {
+            // units attacking towers are drawn in orange
+            let color = if unit.is_some() { ORANGE } else { RED };
             draw_line(
-                target_position.x as f32,
-                target_position.y as f32,
-                position.x as f32,
-                position.y as f32,
+                target_position.x,
+                target_position.y,
+                position.x,
+                position.y,
                 LASER_WIDTH,
-                RED,
+                color,
             );
         }
     }
 }
 
-fn draw_waypoints(waypoints: &[components::Position]) {
-    for (p0, p1) in waypoints.iter().zip(waypoints.iter().skip(1)) {
-        draw_line(
-            p0.x as f32,
-            p0.y as f32,
-            p1.x as f32,
-            p1.y as f32,
+// every lane of the map, spawn nodes are circled in green and goal zones in red
+fn draw_waypoints(map: &Map) {
+    for (p0, p1) in map.edges() {
+        draw_line(p0.x, p0.y, p1.x, p1.y, WAYPOINTS_WIDTH, BLACK);
+    }
+    for position in map.spawns().iter().filter_map(|&id| map.position(id)) {
+        draw_circle_lines(position.x, position.y, NODE_RADIUS, WAYPOINTS_WIDTH, GREEN);
+    }
+    for zone in map.goal_zones() {
+        draw_circle_lines(
+            zone.center.x,
+            zone.center.y,
+            zone.radius,
             WAYPOINTS_WIDTH,
-            BLACK,
+            RED,
         );
     }
 }
 
 fn draw_selection(selection: &Selection) {
     if let Some(position) = &selection.position {
-        if let Some(range) = &selection.range {
+        if let (SelectionStatus::Alive, Some(range)) = (&selection.status, &selection.range) {
             draw_circle_lines(
-                position.x as f32,
-                position.y as f32,
-                (range.squared as f32).sqrt(),
+                position.x,
+                position.y,
+                range.squared.sqrt(),
                 RANGE_WIDTH,
                 BLACK,
             );
         }
+        draw_circle_lines(position.x, position.y, SELECTION_RADIUS, RANGE_WIDTH, BLACK);
+    }
+}
 
-        let mut description: Vec<String> = vec![];
-        description.push(format!("position: {:?}", position));
-        if let Some(damage) = &selection.damage {
-            description.push(format!("damage: {:?}", damage.0));
-        }
-        if let Some(health) = &selection.health {
-            description.push(format!("health: {:?}/{:?}", health.value, health.max));
-        }
-        if let Some(speed) = &selection.speed {
-            description.push(format!("speed: {}", speed.0));
-        }
-        if let Some(score) = &selection.score {
-            description.push(format!("score: {}", score.0));
-        }
-        if !description.is_empty() {
-            draw_text(
-                &description.join(","),
-                position.x as f32,
-                position.y as f32,
-                20.0,
-                BLACK,
-            );
-        }
+// Ghost of the tower about to be built with its range and the part of the path it covers,
+// returns the covered path length. It is red when the spot is taken or gold is short
+fn draw_build_preview(position: Vec2, kind: TowerKind, map: &Map, buildable: bool) -> f32 {
+    let (_damage, range) = spawns::tower_stats(kind);
+    let radius = range.squared.sqrt();
+    let color = if buildable {
+        Color::new(0.0, 0.9, 0.0, 0.5)
+    } else {
+        Color::new(0.9, 0.0, 0.0, 0.5)
+    };
+    draw_rectangle(
+        position.x - TOWER_RADIUS * 0.5,
+        position.y - TOWER_RADIUS * 0.5,
+        TOWER_RADIUS,
+        TOWER_RADIUS,
+        color,
+    );
+    draw_circle_lines(position.x, position.y, radius, RANGE_WIDTH, color);
+    let segments = geometry::covered_segments(map.edges(), position, radius);
+    for (a, b) in &segments {
+        draw_line(a.x, a.y, b.x, b.y, COVERAGE_WIDTH, ORANGE);
+    }
+    geometry::path_coverage(&segments)
+}
+
+// Area of the ability about to be cast, red when it cannot be cast yet
+fn draw_ability_preview(position: Vec2, ability: Ability, usable: bool) {
+    let color = if usable {
+        Color::new(0.0, 0.5, 0.9, 0.3)
+    } else {
+        Color::new(0.9, 0.0, 0.0, 0.3)
+    };
+    if let Some(radius) = ability.radius() {
+        draw_circle(position.x, position.y, radius, color);
+        draw_circle_lines(position.x, position.y, radius, RANGE_WIDTH, color);
+    }
+}
+
+// global state of the run included in dumps
+fn global_state<'a>(
+    step: usize,
+    wave: usize,
+    arrived: usize,
+    economy: &Economy,
+    hero: &HeroState,
+    paused: bool,
+    mode: &'a Mode,
+) -> GlobalState<'a> {
+    GlobalState {
+        step,
+        wave,
+        arrived,
+        gold: economy.gold,
+        lives: economy.lives,
+        paused,
+        mode,
+        hero_level: hero.level(),
+        hero_experience: hero.experience(),
+        hero_respawn_at: hero.respawn_at(),
+    }
+}
+
+fn draw_help(keys: &KeyBindings) {
+    let table = keys.table();
+    let line_height = 20.0;
+    let x = 20.0;
+    let y = ui::TOP_BAR_HEIGHT + 80.0;
+    draw_rectangle(
+        x - 10.0,
+        y - line_height,
+        290.0,
+        line_height * (table.len() + 1) as f32,
+        Color::new(1.0, 1.0, 1.0, 0.8),
+    );
+    for (i, (name, input)) in table.iter().enumerate() {
+        draw_text(
+            &format!("{}: {}", name, input),
+            x,
+            y + line_height * i as f32,
+            20.0,
+            BLACK,
+        );
     }
 }
 
@@ -142,137 +349,438 @@ async fn main() -> anyhow::Result<()> {
 
     let config = get_config()?;
 
-    let waypoints = vec![
-        components::Position { x: -1000, y: 1000 },
-        components::Position { x: -1000, y: -1000 },
-        components::Position { x: 1000, y: -1000 },
-        components::Position { x: 1000, y: 0 },
-        components::Position { x: 0, y: 0 },
-    ];
-    let start = waypoints.first().expect("waypoints not empty");
-    let end = waypoints.last().expect("waypoints not empty");
+    let map = map::default_map();
 
     let mut world = World::new();
-    let mut zoom = 0.001;
-    let mut camera_target = (0., 0.);
-    let mut pause: bool = config.paused;
-    let mut debug: bool = false;
+    let mut camera_controller = CameraController::new(&config.display);
+    let minimap = Minimap::new(&map);
+    let mut pause: bool = config.simulation.paused;
+    let mut debug: bool = config.display.debug;
+    let mut help: bool = false;
+    let mut economy = Economy::new(&config.gameplay);
+    let mut shop = TowerKind::Laser;
+    let mut steps_per_frame = config.simulation.steps_per_frame;
+    let mut health_bars = config.display.health_bars;
+    let mut effects = Effects::new(config.display.damage_numbers, config.display.death_effects);
     let mut camera: Camera2D;
     let mut mode: Mode = Mode::View;
+    // mode restored once the aimed ability is cast
+    let mut mode_before_aim: Mode = Mode::View;
+    let mut events = Events::default();
+    let mut wave: usize = 1;
+    let mut abilities = Abilities::default();
+    let mut hero = HeroState::new(&mut world, &map);
+    let mut report_table = ReportTable::default();
 
-    spawns::batch_spawn_units(&mut world, config.units, start);
-    spawns::batch_spawn_towers(&mut world, config.towers);
+    spawns::batch_spawn_units(&mut world, config.gameplay.units, &map);
+    events.push(Event::WaveStarted {
+        wave,
+        units: config.gameplay.units,
+    });
+    spawns::batch_spawn_towers(&mut world, config.gameplay.towers);
 
-    let mut motion_query = PreparedQuery::<(
-        &mut components::Position,
-        &mut components::Waypoint,
-        &components::Speed,
-    )>::default();
+    let mut motion_query = systems::MotionQuery::default();
     let mut step: usize = 0;
     let mut arrived: usize = 0;
-    let mut selection: Option<Selection> = None;
+    let mut selection: Vec<Selection> = vec![];
+    let mut selection_start: Option<Vec2> = None;
 
     loop {
-        match read_camera_action() {
-            None => {}
-            Some(CameraAction::Zoom(z)) => {
-                zoom *= z;
+        match read_camera_action(&config.keybindings) {
+            Some(CameraAction::Drag(screen_position)) => {
+                camera_controller.drag(screen_position);
             }
-            Some(CameraAction::Target(t0, t1)) => {
-                camera_target.0 += t0;
-                camera_target.1 += t1;
+            camera_action => {
+                camera_controller.release();
+                match camera_action {
+                    Some(CameraAction::Zoom(z)) => {
+                        camera_controller.zoom_at_center(z);
+                    }
+                    Some(CameraAction::ZoomAt(z, screen_position)) => {
+                        camera_controller.zoom_at(z, screen_position);
+                    }
+                    Some(CameraAction::Pan(t0, t1)) => {
+                        camera_controller.pan(vec2(t0, t1));
+                    }
+                    Some(CameraAction::FitMap) => {
+                        camera_controller.fit(&map.positions());
+                    }
+                    Some(CameraAction::Drag(_)) | None => {}
+                }
             }
         }
 
-        camera = Camera2D {
-            target: vec2(camera_target.0, camera_target.1),
-            zoom: vec2(zoom, -zoom * screen_width() / screen_height()),
-            ..Default::default()
-        };
+        if economy.is_game_over() {
+            pause = true;
+        }
 
-        match read_simulation_action(&camera, &mode) {
+        camera_controller.update_follow(&world);
+        camera = camera_controller.camera();
+
+        let hud = Hud {
+            gold: economy.gold,
+            lives: economy.lives,
+            wave,
+            steps_per_frame,
+            paused: pause,
+            game_over: economy.is_game_over(),
+            mode: &mode,
+            shop,
+            abilities: &abilities,
+            step,
+            gold_rush_left: economy.gold_rush_left(step),
+            hero_level: hero.level(),
+            hero_respawn: hero.respawn_left(step),
+        };
+        let ui_action = ui::draw_hud(&hud, &selection);
+        let action = if ui_action.is_some() {
+            ui_action
+        } else {
+            let action = read_simulation_action(&config.keybindings, &camera, &mode);
+            match minimap.hovered_world_position() {
+                Some(target) => {
+                    // clicking or dragging on the minimap moves the camera there, the click
+                    // does not reach the world behind it but keys and releases still do
+                    if config.keybindings.select.is_down() && selection_start.is_none() {
+                        camera_controller.jump_to(target);
+                    }
+                    match action {
+                        Some(
+                            Action::Build(_)
+                            | Action::Remove(_)
+                            | Action::SelectStart(_)
+                            | Action::CastAbility(_, Some(_))
+                            | Action::MoveHero(_),
+                        ) => None,
+                        action => action,
+                    }
+                }
+                None => action,
+            }
+        };
+        match action {
             Some(Action::Quit) => {
                 break;
             }
             Some(Action::TogglePause) => {
-                pause = !pause;
+                pause = !pause || economy.is_game_over();
+            }
+            Some(Action::SetSpeed(speed)) => {
+                steps_per_frame = speed;
+            }
+            Some(Action::SelectShop(kind)) => {
+                shop = kind;
+                mode = Mode::Build;
             }
             Some(Action::Spawn) => {
-                spawns::batch_spawn_units(&mut world, config.units, start);
+                spawns::batch_spawn_units(&mut world, config.gameplay.units, &map);
+                wave += 1;
+                events.push(Event::WaveStarted {
+                    wave,
+                    units: config.gameplay.units,
+                });
             }
             Some(Action::ToggleDebug) => {
                 debug = !debug;
             }
-            Some(Action::PrintState) => {
-                print_world_state(&mut world);
+            Some(Action::ToggleHelp) => {
+                help = !help;
+            }
+            Some(Action::ToggleHealthBars) => {
+                health_bars = !health_bars;
+            }
+            Some(Action::ToggleDamageNumbers) => {
+                effects.toggle_damage_numbers();
+            }
+            Some(Action::ToggleDeathEffects) => {
+                effects.toggle_death_effects();
+            }
+            Some(Action::ToggleFollow) => {
+                camera_controller.toggle_follow(selection.first().map(|s| s.entity));
+            }
+            Some(Action::Dump) => {
+                let global = global_state(step, wave, arrived, &economy, &hero, pause, &mode);
+                dump::write_dump(&world, &map, global, &config.output.dump_dir);
             }
             Some(Action::Build(build_position)) => {
-                spawns::spawn_tower(&mut world, &build_position);
+                if !spawns::can_build(&world, &build_position) {
+                    info!("cannot build on top of a tower or a ruin");
+                } else if economy.try_spend(shop.cost()) {
+                    let tower = spawns::spawn_tower(&mut world, &build_position, shop);
+                    events.push(Event::TowerBuilt {
+                        tower,
+                        position: build_position.into(),
+                    });
+                } else {
+                    info!("not enough gold to build a {:?} tower", shop);
+                }
+            }
+            Some(Action::SelectStart(start_position)) => {
+                selection_start = Some(start_position);
             }
-            Some(Action::View(view_position)) => {
-                selection = spawns::closest_entity(&mut world, &view_position);
+            Some(Action::SelectEnd(end_position, additive)) => {
+                if let Some(start_position) = selection_start.take() {
+                    let on_screen = camera.world_to_screen(start_position)
+                        - camera.world_to_screen(end_position);
+                    let picked = if on_screen.length() < CLICK_TOLERANCE {
+                        spawns::closest_entity(&world, &end_position)
+                            .into_iter()
+                            .collect()
+                    } else {
+                        spawns::entities_in_box(&world, &start_position, &end_position)
+                    };
+                    if !additive {
+                        selection.clear();
+                    }
+                    for picked in picked {
+                        if !selection.iter().any(|s| s.entity == picked.entity) {
+                            selection.push(picked);
+                        }
+                    }
+                }
+            }
+            Some(Action::SellSelected) => {
+                for selected in &selection {
+                    if let Some((position, refund, stats)) =
+                        spawns::sell_tower(&mut world, selected.entity)
+                    {
+                        events.push(Event::TowerSold {
+                            tower: selected.entity,
+                            position,
+                            refund,
+                            stats,
+                        });
+                    }
+                }
+            }
+            Some(Action::UpgradeSelected) => {
+                for selected in &selection {
+                    spawns::upgrade_tower(&mut world, selected.entity, &mut economy);
+                }
+            }
+            Some(Action::RepairSelected) => {
+                for selected in &selection {
+                    spawns::repair_tower(&mut world, selected.entity, &mut economy);
+                }
+            }
+            Some(Action::RebuildSelected) => {
+                for selected in selection.iter_mut() {
+                    if let Some((tower, position, _)) =
+                        spawns::rebuild_tower(&mut world, selected.entity, &mut economy)
+                    {
+                        events.push(Event::TowerBuilt { tower, position });
+                        // the selection moves from the ruin to the new tower
+                        *selected = spawns::get_selection(&world, tower);
+                    }
+                }
+            }
+            Some(Action::CycleTargeting) => {
+                // all selected towers switch to the mode following the first one's
+                if let Some(targeting) = selection.iter().find_map(|s| s.targeting) {
+                    for selected in &selection {
+                        spawns::set_targeting(&mut world, selected.entity, targeting.next());
+                    }
+                }
             }
             Some(Action::Remove(remove_position)) => {
-                spawns::remove_tower(&mut world, &remove_position);
+                if let Some((tower, position, refund, stats)) =
+                    spawns::remove_tower(&mut world, &remove_position)
+                {
+                    events.push(Event::TowerSold {
+                        tower,
+                        position,
+                        refund,
+                        stats,
+                    });
+                }
+            }
+            Some(Action::MoveHero(destination)) => {
+                hero.order_move(&mut world, destination.into());
+            }
+            Some(Action::SelectAbility(ability)) => {
+                selection_start = None;
+                if !matches!(mode, Mode::Aim(_)) {
+                    mode_before_aim = mode;
+                }
+                mode = Mode::Aim(ability);
+            }
+            Some(Action::CastAbility(ability, target)) => {
+                if !abilities.is_ready(ability, step) {
+                    info!(
+                        "{:?} is ready in {} steps",
+                        ability,
+                        abilities.cooldown_left(ability, step)
+                    );
+                } else if economy.try_spend(ability.cost()) {
+                    abilities::cast(&mut world, ability, target.map(Into::into), &mut events);
+                    abilities.start_cooldown(ability, step);
+                    if let Mode::Aim(_) = mode {
+                        mode = mode_before_aim;
+                    }
+                } else {
+                    info!("not enough gold to cast {:?}", ability);
+                }
             }
             Some(Action::ChangeMode(new_mode)) => {
+                selection_start = None;
                 mode = new_mode;
             }
             None => {}
         };
 
         if !pause {
-            systems::system_integrate_motion(&mut world, &mut motion_query, waypoints.as_slice());
-            systems::system_remove_dead(&mut world);
-            let removed = systems::system_remove_arrived(&mut world, end);
-            arrived += removed;
-            systems::system_fire_at_closest(&mut world);
-            step += 1;
+            for _ in 0..steps_per_frame {
+                systems::system_separate_units(&mut world);
+                systems::system_integrate_motion(&mut world, &mut motion_query, &map);
+                systems::system_move_heroes(&mut world);
+                systems::system_remove_dead(&mut world);
+                let removed = systems::system_remove_arrived(&mut world, &map, &mut events);
+                arrived += removed;
+                systems::system_fire_at_closest(&mut world, &mut events);
+                systems::system_hero_attack(&mut world, &mut events);
+                abilities::system_projectile_impacts(&mut world, &mut events);
+                systems::system_units_attack_towers(&mut world, &mut events);
+                systems::system_update_stuns(&mut world);
+                step += 1;
+                events.set_step(step);
+                if config.output.dump_at_steps.contains(&step) {
+                    let global = global_state(step, wave, arrived, &economy, &hero, pause, &mode);
+                    dump::write_dump(&world, &map, global, &config.output.dump_dir);
+                }
+            }
+        }
+
+        for (event_step, event) in events.drain() {
+            debug!("step {}: {}", event_step, event);
+            economy.on_event(&event, event_step);
+            effects.on_event(&event);
+            hero.on_event(&event, event_step);
+            report_table.on_event(&event);
+            for selected in selection.iter_mut() {
+                selected.on_event(&event, event_step);
+            }
         }
+        hero.update(&mut world, step, &mut events);
 
         clear_background(WHITE);
 
         set_camera(&camera);
-        draw_world(&world);
+        effects.update();
+        draw_world(&world, health_bars);
+        effects.draw_deaths();
         if debug {
-            draw_waypoints(waypoints.as_slice());
+            draw_waypoints(&map);
         }
-        match &selection {
-            None => {}
-            Some(selection) => {
-                // update selection
-                let updated_selection = spawns::get_selection(&mut world, selection.entity);
-                draw_selection(&updated_selection);
+        for selected in selection.iter_mut() {
+            selected.update(&world, step);
+        }
+        if selection.len() > 1 {
+            // only single selections keep reporting what happened to their entity
+            selection.retain(|s| matches!(s.status, SelectionStatus::Alive));
+        }
+        for selected in &selection {
+            draw_selection(selected);
+        }
+        if let Some(start_position) = selection_start {
+            let end_position = camera.screen_to_world(Vec2::from(mouse_position()));
+            let min = start_position.min(end_position);
+            let size = (end_position - start_position).abs();
+            draw_rectangle_lines(min.x, min.y, size.x, size.y, RANGE_WIDTH, BLACK);
+        }
+        if let (Mode::Hero, Some(entity)) = (mode, hero.entity()) {
+            // the hero range is shown while ordering it around
+            if let Ok(mut query) =
+                world.query_one::<(&components::Position, &components::Range)>(entity)
+            {
+                if let Some((position, range)) = query.get() {
+                    draw_circle_lines(
+                        position.x,
+                        position.y,
+                        range.squared.sqrt(),
+                        RANGE_WIDTH,
+                        PURPLE,
+                    );
+                }
+            }
+        }
+        let mut cursor_label = None;
+        if !is_mouse_over_ui() && !minimap.is_mouse_over() {
+            let cursor = camera.screen_to_world(Vec2::from(mouse_position()));
+            match mode {
+                Mode::Build => {
+                    let buildable =
+                        shop.cost() <= economy.gold && spawns::can_build(&world, &cursor);
+                    let coverage = draw_build_preview(cursor, shop, &map, buildable);
+                    cursor_label = Some(format!(
+                        "{:?} ({} gold), path covered: {:.0}",
+                        shop,
+                        shop.cost(),
+                        coverage
+                    ));
+                }
+                Mode::Aim(ability) => {
+                    let cooldown = abilities.cooldown_left(ability, step);
+                    let usable = cooldown == 0 && ability.cost() <= economy.gold;
+                    draw_ability_preview(cursor, ability, usable);
+                    cursor_label = Some(if cooldown > 0 {
+                        format!("{:?} ready in {} steps", ability, cooldown)
+                    } else {
+                        format!("{:?} ({} gold)", ability, ability.cost())
+                    });
+                }
+                _ => {}
             }
         }
 
         set_default_camera();
+        effects.draw_damage_numbers(&camera);
+        minimap.draw(&world, &map, &camera);
+        if let Some(label) = cursor_label {
+            let (x, y) = mouse_position();
+            draw_text(&label, x + 15.0, y - 15.0, 20.0, BLACK);
+        }
+        if help {
+            draw_help(&config.keybindings);
+        }
         if debug {
             let units = systems::system_units_left(&world);
             draw_text(
                 &format!("units: {}, arrived: {}, mode: {:?}", units, arrived, mode),
                 20.0,
-                20.0,
+                ui::TOP_BAR_HEIGHT + 20.0,
                 30.0,
                 DARKGRAY,
             );
             draw_text(
                 &format!(
-                    "fps: {} step: {} zoom: {} camera: {:?}",
+                    "fps: {} step: {} zoom: {} camera: {:?} following: {:?}",
                     get_fps(),
                     step,
-                    zoom,
-                    camera_target
+                    camera_controller.zoom,
+                    camera_controller.target,
+                    camera_controller.following()
                 ),
                 20.0,
-                40.0,
+                ui::TOP_BAR_HEIGHT + 40.0,
                 30.0,
                 DARKGRAY,
             );
         }
         next_frame().await;
     }
+    if config.output.dump_on_exit {
+        let global = global_state(step, wave, arrived, &economy, &hero, pause, &mode);
+        dump::write_dump(&world, &map, global, &config.output.dump_dir);
+    }
     let score = systems::system_score(&world);
     info!("score: {}", score);
+    let report = report::collect_report(&world, report_table);
+    report::print_report(&report, step);
+    if let Some(path) = &config.output.report_json {
+        report::write_json(&report, path)?;
+    }
+    if let Some(path) = &config.output.report_csv {
+        report::write_csv(&report, path)?;
+    }
     Ok(())
 }
@@ -0,0 +1,528 @@
+use crate::components::{Distance, Position};
+use crate::geometry::{closest, distance, in_circle};
+use rand::Rng;
+use serde::Serialize;
+
+pub type NodeId = usize;
+
+// points sampled on each span of a curve
+const CURVE_SAMPLES: usize = 16;
+
+// Shape of a branch between its two nodes
+#[derive(Clone, Debug, Default, Serialize)]
+pub enum Curve {
+    #[default]
+    Straight,
+    // control points between the two nodes
+    Bezier(Vec<Position>),
+    // points the curve passes through between the two nodes
+    CatmullRom(Vec<Position>),
+}
+
+// Edge toward another node, forks pick one of their branches proportionally to its weight
+#[derive(Clone, Debug, Serialize)]
+pub struct Branch {
+    pub to: NodeId,
+    pub weight: u32,
+    pub curve: Curve,
+}
+
+// Nodes without branches are goals, units reaching them leak
+#[derive(Clone, Debug, Serialize)]
+pub struct Node {
+    pub position: Position,
+    pub branches: Vec<Branch>,
+}
+
+// Area where units leak, every goal node lies in one
+#[derive(Clone, Debug, Serialize)]
+pub struct GoalZone {
+    pub center: Position,
+    pub radius: Distance,
+}
+
+impl GoalZone {
+    pub fn contains(&self, position: &Position) -> bool {
+        in_circle(position, &self.center, self.radius)
+    }
+}
+
+// Waypoint graph: lanes start at spawn nodes, may fork and merge, and end at goal nodes
+#[derive(Debug, Serialize)]
+pub struct Map {
+    nodes: Vec<Node>,
+    spawns: Vec<NodeId>,
+    goal_zones: Vec<GoalZone>,
+    // shortest path distance from each node to a goal
+    #[serde(skip)]
+    to_goal: Vec<Distance>,
+}
+
+impl Map {
+    // curved branches are sampled into chains of extra nodes, so that motion,
+    // progress and coverage follow the curves and measure their arc length
+    pub fn new(
+        mut nodes: Vec<Node>,
+        spawns: Vec<NodeId>,
+        goal_zones: Vec<GoalZone>,
+    ) -> anyhow::Result<Self> {
+        if spawns.is_empty() {
+            anyhow::bail!("the map has no spawn node");
+        }
+        for &id in &spawns {
+            if id >= nodes.len() {
+                anyhow::bail!("spawn node {} does not exist", id);
+            }
+        }
+        for (id, node) in nodes.iter().enumerate() {
+            for branch in &node.branches {
+                if branch.to >= nodes.len() {
+                    anyhow::bail!("node {} branches to missing node {}", id, branch.to);
+                }
+            }
+            if !node.branches.is_empty() && node.branches.iter().all(|b| b.weight == 0) {
+                anyhow::bail!("node {} only has branches of weight 0", id);
+            }
+            if node.branches.is_empty() && !goal_zones.iter().any(|z| z.contains(&node.position)) {
+                anyhow::bail!("goal node {} is outside of every goal zone", id);
+            }
+        }
+        sample_curves(&mut nodes);
+        let to_goal = distances_to_goal(&nodes);
+        for &id in &spawns {
+            if to_goal[id] == Distance::MAX {
+                anyhow::bail!("spawn node {} does not lead to a goal", id);
+            }
+        }
+        Ok(Map {
+            nodes,
+            spawns,
+            goal_zones,
+            to_goal,
+        })
+    }
+
+    pub fn spawns(&self) -> &[NodeId] {
+        &self.spawns
+    }
+
+    pub fn position(&self, id: NodeId) -> Option<&Position> {
+        self.nodes.get(id).map(|node| &node.position)
+    }
+
+    pub fn goal_zones(&self) -> &[GoalZone] {
+        &self.goal_zones
+    }
+
+    pub fn in_goal_zone(&self, position: &Position) -> bool {
+        self.goal_zones.iter().any(|zone| zone.contains(position))
+    }
+
+    // goal closest to `position` as the crow flies
+    pub fn closest_goal(&self, position: &Position) -> Option<NodeId> {
+        let goals = self
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| node.branches.is_empty())
+            .map(|(id, node)| (id, &node.position));
+        closest(goals, position, Distance::MAX)
+    }
+
+    pub fn positions(&self) -> Vec<Position> {
+        self.nodes
+            .iter()
+            .map(|node| node.position.clone())
+            .collect()
+    }
+
+    // every lane segment, shared segments of merged lanes appear once
+    pub fn edges(&self) -> impl Iterator<Item = (&Position, &Position)> {
+        self.nodes.iter().flat_map(move |node| {
+            node.branches
+                .iter()
+                .map(move |branch| (&node.position, &self.nodes[branch.to].position))
+        })
+    }
+
+    // weighted random branch of `id`, None for goals
+    pub fn choose_next<R: Rng>(&self, id: NodeId, rng: &mut R) -> Option<NodeId> {
+        let branches = &self.nodes.get(id)?.branches;
+        let total: u32 = branches.iter().map(|b| b.weight).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut pick = rng.gen_range(0..total);
+        for branch in branches {
+            if pick < branch.weight {
+                return Some(branch.to);
+            }
+            pick -= branch.weight;
+        }
+        None
+    }
+
+    // Distance from `position`, heading to node `id`, to a goal along the shortest lane.
+    // Units pick their branch only when reaching a fork, so past the next fork this is a
+    // lower bound of what the unit will actually walk
+    pub fn shortest_remaining_distance(&self, position: &Position, id: NodeId) -> Distance {
+        match (self.position(id), self.to_goal.get(id)) {
+            (Some(node), Some(to_goal)) => distance(node, position) + to_goal,
+            _ => 0.0,
+        }
+    }
+}
+
+fn lerp(a: &Position, b: &Position, t: f32) -> Position {
+    Position {
+        x: a.x + (b.x - a.x) * t,
+        y: a.y + (b.y - a.y) * t,
+    }
+}
+
+// de Casteljau evaluation
+fn bezier(points: &[Position], t: f32) -> Position {
+    let mut points = points.to_vec();
+    while points.len() > 1 {
+        points = points
+            .iter()
+            .zip(points.iter().skip(1))
+            .map(|(a, b)| lerp(a, b, t))
+            .collect();
+    }
+    points[0].clone()
+}
+
+// centripetal parametrization is not needed for the hand made maps, uniform is used
+fn catmull_rom(p0: &Position, p1: &Position, p2: &Position, p3: &Position, t: f32) -> Position {
+    let (t2, t3) = (t * t, t * t * t);
+    let axis = |a: f32, b: f32, c: f32, d: f32| {
+        0.5 * (2.0 * b
+            + (c - a) * t
+            + (2.0 * a - 5.0 * b + 4.0 * c - d) * t2
+            + (3.0 * b - a - 3.0 * c + d) * t3)
+    };
+    Position {
+        x: axis(p0.x, p1.x, p2.x, p3.x),
+        y: axis(p0.y, p1.y, p2.y, p3.y),
+    }
+}
+
+// points strictly between `from` and `to` along the curve
+fn sample_curve(from: &Position, to: &Position, curve: &Curve) -> Vec<Position> {
+    match curve {
+        Curve::Straight => vec![],
+        Curve::Bezier(controls) => {
+            let mut points = vec![from.clone()];
+            points.extend(controls.iter().cloned());
+            points.push(to.clone());
+            let samples = CURVE_SAMPLES * (controls.len() + 1);
+            (1..samples)
+                .map(|i| bezier(&points, i as f32 / samples as f32))
+                .collect()
+        }
+        Curve::CatmullRom(through) => {
+            // the end points are repeated so that the curve starts and ends at the nodes
+            let mut points = vec![from.clone(), from.clone()];
+            points.extend(through.iter().cloned());
+            points.push(to.clone());
+            points.push(to.clone());
+            let mut samples = vec![];
+            for span in points.windows(4) {
+                for i in 0..CURVE_SAMPLES {
+                    let t = i as f32 / CURVE_SAMPLES as f32;
+                    samples.push(catmull_rom(&span[0], &span[1], &span[2], &span[3], t));
+                }
+            }
+            // the first sample is `from` itself
+            samples.remove(0);
+            samples
+        }
+    }
+}
+
+fn sample_curves(nodes: &mut Vec<Node>) {
+    for id in 0..nodes.len() {
+        for b in 0..nodes[id].branches.len() {
+            let branch = &nodes[id].branches[b];
+            let samples = sample_curve(
+                &nodes[id].position,
+                &nodes[branch.to].position,
+                &branch.curve,
+            );
+            if samples.is_empty() {
+                continue;
+            }
+            let to = branch.to;
+            let first = nodes.len();
+            let count = samples.len();
+            for (i, position) in samples.into_iter().enumerate() {
+                let next = if i + 1 < count { first + i + 1 } else { to };
+                nodes.push(Node {
+                    position,
+                    branches: vec![Branch {
+                        to: next,
+                        weight: 1,
+                        curve: Curve::Straight,
+                    }],
+                });
+            }
+            let branch = &mut nodes[id].branches[b];
+            branch.to = first;
+            branch.curve = Curve::Straight;
+        }
+    }
+}
+
+// Bellman-Ford relaxation, maps are small
+fn distances_to_goal(nodes: &[Node]) -> Vec<Distance> {
+    let mut to_goal: Vec<Distance> = nodes
+        .iter()
+        .map(|node| {
+            if node.branches.is_empty() {
+                0.0
+            } else {
+                Distance::MAX
+            }
+        })
+        .collect();
+    for _ in 0..nodes.len() {
+        let mut changed = false;
+        for (id, node) in nodes.iter().enumerate() {
+            for branch in node.branches.iter().filter(|b| b.weight > 0) {
+                if to_goal[branch.to] == Distance::MAX {
+                    continue;
+                }
+                let length = distance(&nodes[branch.to].position, &node.position);
+                if to_goal[branch.to] + length < to_goal[id] {
+                    to_goal[id] = to_goal[branch.to] + length;
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    to_goal
+}
+
+fn node(x: Distance, y: Distance, branches: Vec<Branch>) -> Node {
+    Node {
+        position: Position { x, y },
+        branches,
+    }
+}
+
+fn branch(to: NodeId, weight: u32, curve: Curve) -> Branch {
+    Branch { to, weight, curve }
+}
+
+fn point(x: Distance, y: Distance) -> Position {
+    Position { x, y }
+}
+
+// Two lanes merging before the goal, the west lane forks into a long and a short curved route
+pub fn default_map() -> Map {
+    let nodes = vec![
+        node(-1000.0, 1000.0, vec![branch(1, 1, Curve::Straight)]),
+        node(
+            -1000.0,
+            -1000.0,
+            vec![
+                branch(2, 2, Curve::Straight),
+                branch(5, 1, Curve::Bezier(vec![point(-1000.0, -400.0)])),
+            ],
+        ),
+        node(1000.0, -1000.0, vec![branch(3, 1, Curve::Straight)]),
+        node(1000.0, 0.0, vec![branch(4, 1, Curve::Straight)]),
+        node(0.0, 0.0, vec![]),
+        node(-300.0, -300.0, vec![branch(4, 1, Curve::Straight)]),
+        node(
+            1000.0,
+            1000.0,
+            vec![branch(
+                3,
+                1,
+                Curve::CatmullRom(vec![point(1400.0, 700.0), point(700.0, 350.0)]),
+            )],
+        ),
+    ];
+    let goal_zones = vec![GoalZone {
+        center: point(0.0, 0.0),
+        radius: 60.0,
+    }];
+    Map::new(nodes, vec![0, 6], goal_zones).expect("default map is valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::thread_rng;
+
+    fn zone(x: Distance, y: Distance) -> GoalZone {
+        GoalZone {
+            center: point(x, y),
+            radius: 10.0,
+        }
+    }
+
+    fn straight(to: NodeId) -> Vec<Branch> {
+        vec![branch(to, 1, Curve::Straight)]
+    }
+
+    fn error(result: anyhow::Result<Map>) -> String {
+        result.expect_err("the map is invalid").to_string()
+    }
+
+    #[test]
+    fn new_rejects_invalid_graphs() {
+        let goal = || node(0.0, 0.0, vec![]);
+        assert_eq!(
+            error(Map::new(vec![goal()], vec![], vec![zone(0.0, 0.0)])),
+            "the map has no spawn node"
+        );
+        assert_eq!(
+            error(Map::new(vec![goal()], vec![3], vec![zone(0.0, 0.0)])),
+            "spawn node 3 does not exist"
+        );
+        assert_eq!(
+            error(Map::new(
+                vec![node(5.0, 5.0, straight(7)), goal()],
+                vec![0],
+                vec![zone(0.0, 0.0)]
+            )),
+            "node 0 branches to missing node 7"
+        );
+        assert_eq!(
+            error(Map::new(
+                vec![node(5.0, 5.0, vec![branch(1, 0, Curve::Straight)]), goal()],
+                vec![0],
+                vec![zone(0.0, 0.0)]
+            )),
+            "node 0 only has branches of weight 0"
+        );
+        assert_eq!(
+            error(Map::new(
+                vec![node(5.0, 5.0, straight(1)), node(100.0, 0.0, vec![])],
+                vec![0],
+                vec![zone(0.0, 0.0)]
+            )),
+            "goal node 1 is outside of every goal zone"
+        );
+        // nodes 0 and 1 loop forever, the goal is unreachable from them
+        assert_eq!(
+            error(Map::new(
+                vec![
+                    node(5.0, 5.0, straight(1)),
+                    node(9.0, 9.0, straight(0)),
+                    goal()
+                ],
+                vec![0],
+                vec![zone(0.0, 0.0)]
+            )),
+            "spawn node 0 does not lead to a goal"
+        );
+    }
+
+    #[test]
+    fn distances_to_goal_follow_the_shortest_lane() {
+        let nodes = vec![
+            node(
+                0.0,
+                0.0,
+                vec![branch(1, 1, Curve::Straight), branch(2, 1, Curve::Straight)],
+            ),
+            node(3.0, 4.0, straight(3)),
+            node(3.0, 100.0, straight(3)),
+            node(3.0, 10.0, vec![]),
+        ];
+        assert_eq!(distances_to_goal(&nodes), vec![11.0, 6.0, 90.0, 0.0]);
+    }
+
+    #[test]
+    fn shortest_remaining_distance_adds_the_way_to_the_next_node() {
+        let map = Map::new(
+            vec![node(0.0, 0.0, straight(1)), node(0.0, 10.0, vec![])],
+            vec![0],
+            vec![zone(0.0, 10.0)],
+        )
+        .unwrap();
+        assert_eq!(map.shortest_remaining_distance(&point(3.0, 6.0), 1), 5.0);
+        assert_eq!(map.shortest_remaining_distance(&point(0.0, 0.0), 0), 10.0);
+    }
+
+    #[test]
+    fn curves_exclude_their_end_points() {
+        let (from, to) = (point(0.0, 0.0), point(100.0, 0.0));
+        let bezier = sample_curve(&from, &to, &Curve::Bezier(vec![point(50.0, 50.0)]));
+        assert_eq!(bezier.len(), 2 * CURVE_SAMPLES - 1);
+        let catmull_rom = sample_curve(&from, &to, &Curve::CatmullRom(vec![point(50.0, 50.0)]));
+        assert_eq!(catmull_rom.len(), 2 * CURVE_SAMPLES - 1);
+        for samples in [&bezier, &catmull_rom] {
+            assert_ne!(samples.first(), Some(&from));
+            assert_ne!(samples.last(), Some(&to));
+        }
+        // the Catmull-Rom curve passes through its control point
+        assert!(catmull_rom
+            .iter()
+            .any(|sample| (sample - &point(50.0, 50.0)).norm() < 1e-3));
+        assert!(sample_curve(&from, &to, &Curve::Straight).is_empty());
+    }
+
+    #[test]
+    fn sampled_curves_measure_their_arc_length() {
+        let map = |curve: Curve| {
+            Map::new(
+                vec![
+                    node(0.0, 0.0, vec![branch(1, 1, curve)]),
+                    node(100.0, 0.0, vec![]),
+                ],
+                vec![0],
+                vec![zone(100.0, 0.0)],
+            )
+            .unwrap()
+        };
+        // a control point on the segment keeps the curve straight
+        let flat = map(Curve::Bezier(vec![point(50.0, 0.0)]));
+        let length = flat.shortest_remaining_distance(&point(0.0, 0.0), 0);
+        assert!((length - 100.0).abs() < 1e-3);
+        // the quadratic curve through (50, 25) is longer than the straight segment
+        // and shorter than the polygon through its control point
+        let arc = map(Curve::Bezier(vec![point(50.0, 50.0)]));
+        let length = arc.shortest_remaining_distance(&point(0.0, 0.0), 0);
+        assert!(length > 100.0 && length < 2.0 * 50.0 * 2f32.sqrt());
+        assert_eq!(arc.positions().len(), 2 + 2 * CURVE_SAMPLES - 1);
+    }
+
+    #[test]
+    fn choose_next_skips_branches_of_weight_0() {
+        let map = Map::new(
+            vec![
+                node(
+                    0.0,
+                    0.0,
+                    vec![branch(1, 0, Curve::Straight), branch(2, 3, Curve::Straight)],
+                ),
+                node(-50.0, 0.0, vec![]),
+                node(50.0, 0.0, vec![]),
+            ],
+            vec![0],
+            vec![zone(-50.0, 0.0), zone(50.0, 0.0)],
+        )
+        .unwrap();
+        let mut rng = thread_rng();
+        for _ in 0..100 {
+            assert_eq!(map.choose_next(0, &mut rng), Some(2));
+        }
+        assert_eq!(map.choose_next(2, &mut rng), None);
+    }
+
+    #[test]
+    fn goals_and_goal_zones() {
+        let map = default_map();
+        assert!(map.in_goal_zone(&point(30.0, 30.0)));
+        assert!(!map.in_goal_zone(&point(100.0, 0.0)));
+        assert_eq!(map.closest_goal(&point(-1000.0, 1000.0)), Some(4));
+    }
+}
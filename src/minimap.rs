@@ -0,0 +1,128 @@
+use crate::components::{Health, Hero, Position, Tower, Unit};
+use crate::geometry::to_vec2;
+use crate::map::Map;
+use hecs::{With, World};
+use macroquad::prelude::{
+    draw_line, mouse_position, screen_height, screen_width, vec2, Camera2D, Color, Rect, Vec2,
+    BLACK, GREEN, PURPLE,
+};
+use macroquad::shapes::{draw_circle, draw_rectangle, draw_rectangle_lines};
+
+// side of the minimap on screen (pixels)
+const MINIMAP_SIZE: f32 = 200.0;
+// distance to the bottom left corner of the screen (pixels)
+const MINIMAP_MARGIN: f32 = 10.0;
+// extra space around the map nodes
+const MINIMAP_PADDING: f32 = 1.2;
+const MINIMAP_UNIT_RADIUS: f32 = 2.0;
+const MINIMAP_TOWER_SIZE: f32 = 4.0;
+const MINIMAP_LINE_WIDTH: f32 = 1.0;
+
+// Screen space overview of the whole map, bounds are fixed by the map nodes
+pub struct Minimap {
+    center: Vec2,
+    // world distance per minimap pixel
+    scale: f32,
+}
+
+impl Minimap {
+    pub fn new(map: &Map) -> Self {
+        let points: Vec<Vec2> = map.positions().iter().map(to_vec2).collect();
+        let min = points.iter().fold(Vec2::splat(f32::MAX), |m, p| m.min(*p));
+        let max = points.iter().fold(Vec2::splat(f32::MIN), |m, p| m.max(*p));
+        let (center, extent) = if points.is_empty() {
+            (vec2(0., 0.), 1.0)
+        } else {
+            let size = max - min;
+            ((min + max) * 0.5, size.x.max(size.y).max(1.0))
+        };
+        Minimap {
+            center,
+            scale: extent * MINIMAP_PADDING / MINIMAP_SIZE,
+        }
+    }
+
+    fn rect(&self) -> Rect {
+        Rect::new(
+            MINIMAP_MARGIN,
+            screen_height() - MINIMAP_SIZE - MINIMAP_MARGIN,
+            MINIMAP_SIZE,
+            MINIMAP_SIZE,
+        )
+    }
+
+    fn world_to_minimap(&self, position: Vec2) -> Vec2 {
+        self.rect().center() + (position - self.center) / self.scale
+    }
+
+    fn minimap_to_world(&self, screen_position: Vec2) -> Vec2 {
+        self.center + (screen_position - self.rect().center()) * self.scale
+    }
+
+    pub fn is_mouse_over(&self) -> bool {
+        self.rect().contains(Vec2::from(mouse_position()))
+    }
+
+    // world position under the cursor if it is over the minimap
+    pub fn hovered_world_position(&self) -> Option<Vec2> {
+        let cursor = Vec2::from(mouse_position());
+        if self.rect().contains(cursor) {
+            Some(self.minimap_to_world(cursor))
+        } else {
+            None
+        }
+    }
+
+    pub fn draw(&self, world: &World, map: &Map, camera: &Camera2D) {
+        let rect = self.rect();
+        draw_rectangle(
+            rect.x,
+            rect.y,
+            rect.w,
+            rect.h,
+            Color::new(1.0, 1.0, 1.0, 0.8),
+        );
+        draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, MINIMAP_LINE_WIDTH, BLACK);
+        for (p0, p1) in map.edges() {
+            let a = self.world_to_minimap(to_vec2(p0));
+            let b = self.world_to_minimap(to_vec2(p1));
+            draw_line(a.x, a.y, b.x, b.y, MINIMAP_LINE_WIDTH, BLACK);
+        }
+        for (_id, position) in world.query::<With<Tower, &Position>>().iter() {
+            let p = self.world_to_minimap(to_vec2(position));
+            if rect.contains(p) {
+                draw_rectangle(
+                    p.x - MINIMAP_TOWER_SIZE * 0.5,
+                    p.y - MINIMAP_TOWER_SIZE * 0.5,
+                    MINIMAP_TOWER_SIZE,
+                    MINIMAP_TOWER_SIZE,
+                    GREEN,
+                );
+            }
+        }
+        for (_id, (health, position)) in world.query::<With<Unit, (&Health, &Position)>>().iter() {
+            let p = self.world_to_minimap(to_vec2(position));
+            if rect.contains(p) {
+                let health_ratio = (health.value as f32 / health.max as f32).clamp(0f32, 1f32);
+                let color = Color::new(health_ratio, 0.0, 1.0 - health_ratio, 1.0f32);
+                draw_circle(p.x, p.y, MINIMAP_UNIT_RADIUS, color);
+            }
+        }
+        for (_id, position) in world.query::<With<Hero, &Position>>().iter() {
+            let p = self.world_to_minimap(to_vec2(position));
+            if rect.contains(p) {
+                draw_circle(p.x, p.y, MINIMAP_UNIT_RADIUS * 2.0, PURPLE);
+            }
+        }
+        // camera viewport, clipped to the minimap
+        let corner = self.world_to_minimap(camera.screen_to_world(vec2(0., 0.)));
+        let opposite_corner =
+            self.world_to_minimap(camera.screen_to_world(vec2(screen_width(), screen_height())));
+        let min = corner.min(opposite_corner).max(rect.point());
+        let max = corner.max(opposite_corner).min(rect.point() + rect.size());
+        if min.x < max.x && min.y < max.y {
+            let size = max - min;
+            draw_rectangle_lines(min.x, min.y, size.x, size.y, MINIMAP_LINE_WIDTH, BLACK);
+        }
+    }
+}
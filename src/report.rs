@@ -0,0 +1,246 @@
+use crate::components::{CombatStats, Position, Tower};
+use crate::events::Event;
+use hecs::{Entity, With, World};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+// What became of a tower by the end of the run
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Alive,
+    Sold,
+    Destroyed,
+}
+
+impl Outcome {
+    fn name(self) -> &'static str {
+        match self {
+            Outcome::Alive => "alive",
+            Outcome::Sold => "sold",
+            Outcome::Destroyed => "destroyed",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TowerReport {
+    pub tower: u64,
+    pub outcome: Outcome,
+    pub x: f32,
+    pub y: f32,
+    pub damage_dealt: i32,
+    pub overkill: i32,
+    pub shots_fired: usize,
+    pub kills: usize,
+    pub steps_with_target: usize,
+}
+
+const CSV_HEADER: &str =
+    "tower,outcome,x,y,damage_dealt,overkill,shots_fired,kills,steps_with_target";
+
+fn tower_report(
+    tower: Entity,
+    outcome: Outcome,
+    position: &Position,
+    stats: &CombatStats,
+) -> TowerReport {
+    TowerReport {
+        tower: tower.to_bits().get(),
+        outcome,
+        x: position.x,
+        y: position.y,
+        damage_dealt: stats.damage_dealt,
+        overkill: stats.overkill,
+        shots_fired: stats.shots_fired,
+        kills: stats.kills,
+        steps_with_target: stats.steps_with_target,
+    }
+}
+
+// Final stats of the towers removed during the run, sold and destroyed towers leave the
+// world before the report is collected
+#[derive(Default)]
+pub struct ReportTable {
+    removed: Vec<TowerReport>,
+}
+
+impl ReportTable {
+    pub fn on_event(&mut self, event: &Event) {
+        match event {
+            Event::TowerSold {
+                tower,
+                position,
+                stats,
+                ..
+            } => {
+                self.removed
+                    .push(tower_report(*tower, Outcome::Sold, position, stats));
+            }
+            Event::TowerDestroyed {
+                tower,
+                position,
+                stats,
+                ..
+            } => {
+                self.removed
+                    .push(tower_report(*tower, Outcome::Destroyed, position, stats));
+            }
+            _ => {}
+        }
+    }
+}
+
+// live and removed towers sorted by damage dealt, best first
+pub fn collect_report(world: &World, table: ReportTable) -> Vec<TowerReport> {
+    let mut report: Vec<TowerReport> = world
+        .query::<With<Tower, (&Position, &CombatStats)>>()
+        .iter()
+        .map(|(id, (position, stats))| tower_report(id, Outcome::Alive, position, stats))
+        .chain(table.removed)
+        .collect();
+    report.sort_by(|a, b| {
+        b.damage_dealt
+            .cmp(&a.damage_dealt)
+            .then(b.kills.cmp(&a.kills))
+    });
+    report
+}
+
+pub fn print_report(report: &[TowerReport], steps: usize) {
+    println!("\nTower report ({} steps):", steps);
+    println!(
+        "{:>20} {:>10} {:>12} {:>8} {:>8} {:>8} {:>6} {:>8}",
+        "tower", "outcome", "position", "damage", "overkill", "shots", "kills", "uptime"
+    );
+    for row in report {
+        let uptime = if steps > 0 {
+            100.0 * row.steps_with_target as f32 / steps as f32
+        } else {
+            0.0
+        };
+        println!(
+            "{:>20} {:>10} {:>12} {:>8} {:>8} {:>8} {:>6} {:>7.1}%",
+            row.tower,
+            row.outcome.name(),
+            format!("{:.0},{:.0}", row.x, row.y),
+            row.damage_dealt,
+            row.overkill,
+            row.shots_fired,
+            row.kills,
+            uptime
+        );
+    }
+}
+
+pub fn write_json(report: &[TowerReport], path: &Path) -> anyhow::Result<()> {
+    let writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(writer, report)?;
+    Ok(())
+}
+
+pub fn write_csv(report: &[TowerReport], path: &Path) -> anyhow::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "{}", CSV_HEADER)?;
+    for row in report {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{},{},{}",
+            row.tower,
+            row.outcome.name(),
+            row.x,
+            row.y,
+            row.damage_dealt,
+            row.overkill,
+            row.shots_fired,
+            row.kills,
+            row.steps_with_target
+        )?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn tower(world: &mut World, x: f32, damage_dealt: i32, kills: usize) -> u64 {
+        let stats = CombatStats {
+            damage_dealt,
+            kills,
+            ..CombatStats::default()
+        };
+        world
+            .spawn((Tower, Position { x, y: 0.0 }, stats))
+            .to_bits()
+            .get()
+    }
+
+    #[test]
+    fn towers_are_sorted_by_damage_then_kills() {
+        let mut world = World::new();
+        let weak = tower(&mut world, 1.0, 10, 5);
+        let strong = tower(&mut world, 2.0, 40, 1);
+        let killer = tower(&mut world, 3.0, 10, 6);
+        let order: Vec<u64> = collect_report(&world, ReportTable::default())
+            .iter()
+            .map(|r| r.tower)
+            .collect();
+        assert_eq!(order, vec![strong, killer, weak]);
+    }
+
+    #[test]
+    fn csv_has_a_header_and_a_row_per_tower() {
+        let mut world = World::new();
+        let id = tower(&mut world, -7.0, 12, 3);
+        let path = std::env::temp_dir().join(format!("td-rs-report-{}.csv", std::process::id()));
+        write_csv(&collect_report(&world, ReportTable::default()), &path).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            content,
+            format!("{}\n{},alive,-7,0,12,0,0,3,0\n", CSV_HEADER, id)
+        );
+    }
+
+    #[test]
+    fn sold_and_destroyed_towers_keep_their_final_stats() {
+        let mut world = World::new();
+        let alive = tower(&mut world, 0.0, 5, 0);
+        let stats = |damage_dealt| CombatStats {
+            damage_dealt,
+            ..CombatStats::default()
+        };
+        let mut table = ReportTable::default();
+        let (sold, destroyed) = (world.spawn(()), world.spawn(()));
+        table.on_event(&Event::TowerSold {
+            tower: sold,
+            position: Position { x: 1.0, y: 0.0 },
+            refund: 10,
+            stats: stats(30),
+        });
+        table.on_event(&Event::TowerDestroyed {
+            tower: destroyed,
+            unit: world.spawn(()),
+            position: Position { x: 2.0, y: 0.0 },
+            stats: stats(20),
+        });
+        table.on_event(&Event::WaveStarted { wave: 1, units: 1 });
+        let report: Vec<(u64, Outcome, i32)> = collect_report(&world, table)
+            .iter()
+            .map(|r| (r.tower, r.outcome, r.damage_dealt))
+            .collect();
+        assert_eq!(
+            report,
+            vec![
+                (sold.to_bits().get(), Outcome::Sold, 30),
+                (destroyed.to_bits().get(), Outcome::Destroyed, 20),
+                (alive, Outcome::Alive, 5),
+            ]
+        );
+    }
+}
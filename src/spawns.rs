@@ -1,121 +1,674 @@
-use crate::components::{Damage, Health, Position, Range, Score, Speed, Target, Waypoint};
+use crate::components::{
+    CombatStats, Damage, EntityKind, Health, Level, Movement, PathProgress, Position, Range, Ruin,
+    Score, Speed, Target, Targeting, Tower, TowerKind, Unit, Value, Waypoint,
+};
+use crate::economy::Economy;
+use crate::events::{DamageSource, Event};
+use crate::geometry::closest;
+use crate::map::Map;
 use hecs::{Entity, With, World};
 use macroquad::prelude::Vec2;
 use rand::{thread_rng, Rng};
+use std::ops::Deref;
+
+// What happened to a selected entity, despawned entities keep their last known components
+#[derive(Clone, Debug)]
+pub enum SelectionStatus {
+    Alive,
+    Killed { step: usize, source: DamageSource },
+    Leaked { step: usize },
+    Sold { step: usize },
+    Destroyed { step: usize, unit: Entity },
+    HeroKilled { step: usize, unit: Entity },
+    Despawned { step: usize },
+}
 
 pub struct Selection {
     pub entity: Entity,
+    pub status: SelectionStatus,
+    pub entity_kind: Option<EntityKind>,
     pub position: Option<Position>,
     pub range: Option<Range>,
     pub damage: Option<Damage>,
     pub speed: Option<Speed>,
     pub health: Option<Health>,
     pub score: Option<Score>,
+    pub targeting: Option<Targeting>,
+    pub level: Option<Level>,
+    pub kind: Option<TowerKind>,
+    pub value: Option<Value>,
+    pub progress: Option<PathProgress>,
+    pub movement: Option<Movement>,
+    pub ruin: Option<Ruin>,
 }
 
-pub fn batch_spawn_units(world: &mut World, units: usize, spawn_position: &Position) {
+// entities farther than this from a click are not picked
+const PICK_RADIUS: f32 = 10.0;
+// fraction of the spawned units that fly
+const FLYING_CHANCE: f64 = 0.2;
+// fraction of the ground units that stop to attack towers
+const SIEGE_CHANCE: f64 = 0.15;
+const SIEGE_DAMAGE: i32 = 2;
+const SIEGE_RANGE_SQUARED: f32 = 2_500.0;
+// gold per health point restored by repairs
+const REPAIR_COST_PER_HEALTH: f32 = 0.25;
+
+type UnitBundle = (
+    Unit,
+    Position,
+    Speed,
+    Health,
+    Waypoint,
+    PathProgress,
+    Movement,
+);
+
+// siege units also carry what they need to attack towers
+type SiegeBundle = (
+    Unit,
+    Position,
+    Speed,
+    Health,
+    Waypoint,
+    PathProgress,
+    Movement,
+    Damage,
+    Range,
+    Target,
+);
+
+// each unit spawns around a random spawn node of the map, flying units head
+// straight to the closest goal instead of following the lanes
+pub fn batch_spawn_units(world: &mut World, units: usize, map: &Map) {
     let mut rng = thread_rng();
-    let to_spawn = (0..units).map(|_| {
+    let mut plain: Vec<UnitBundle> = Vec::new();
+    let mut siege: Vec<SiegeBundle> = Vec::new();
+    for _ in 0..units {
+        let spawn = map.spawns()[rng.gen_range(0..map.spawns().len())];
+        let spawn_position = map.position(spawn).expect("spawn nodes exist");
         let position = Position {
-            x: spawn_position.x + rng.gen_range(-1000..1000),
-            y: spawn_position.y + rng.gen_range(-1000..1000),
+            x: spawn_position.x + rng.gen_range(-1000.0..1000.0),
+            y: spawn_position.y + rng.gen_range(-1000.0..1000.0),
         };
-        let speed = Speed(rng.gen_range(1..5));
+        let speed = Speed(rng.gen_range(1.0..5.0));
         let health_value: i32 = rng.gen_range(30..200);
         let health = Health {
             value: health_value,
             max: health_value,
         };
-        let waypoint = Waypoint { index: 0 };
-        (position, speed, health, waypoint)
-    });
-    world.spawn_batch(to_spawn);
-}
-
-pub fn batch_spawn_towers(world: &mut World, towers: usize) {
-    let mut rng = thread_rng();
-    let to_spawn = (0..towers).map(|_| {
-        let position = Position {
-            x: rng.gen_range(-100..100),
-            y: rng.gen_range(-100..100),
-        };
-        let damage = Damage(rng.gen_range(3..5));
-        let range = Range {
-            squared: rng.gen_range(10_000..20_000),
+        let (movement, node) = if rng.gen_bool(FLYING_CHANCE) {
+            let goal = map.closest_goal(spawn_position).unwrap_or(spawn);
+            (Movement::Flying, goal)
+        } else {
+            (Movement::Ground, spawn)
         };
-        let score = Score(0);
-        let target = Target {
-            position: None,
-            entity: None,
+        let waypoint = Waypoint { node };
+        let progress = PathProgress {
+            traveled: 0.0,
+            remaining: map.shortest_remaining_distance(&position, node),
         };
-        (position, damage, range, score, target)
-    });
-    world.spawn_batch(to_spawn);
+        if movement == Movement::Ground && rng.gen_bool(SIEGE_CHANCE) {
+            let target = Target {
+                position: None,
+                entity: None,
+            };
+            siege.push((
+                Unit,
+                position,
+                speed,
+                health,
+                waypoint,
+                progress,
+                movement,
+                Damage(SIEGE_DAMAGE),
+                Range {
+                    squared: SIEGE_RANGE_SQUARED,
+                },
+                target,
+            ));
+        } else {
+            plain.push((Unit, position, speed, health, waypoint, progress, movement));
+        }
+    }
+    world.spawn_batch(plain);
+    world.spawn_batch(siege);
 }
 
-pub fn spawn_tower(world: &mut World, position: &Vec2) {
-    let mut rng = thread_rng();
-    let position = Position {
-        x: position.x as i32,
-        y: position.y as i32,
-    };
-    let damage = Damage(rng.gen_range(3..5));
-    let range = Range {
-        squared: rng.gen_range(10_000..20_000),
-    };
+// stats of a newly built tower, also used to preview its range before building
+pub fn tower_stats(kind: TowerKind) -> (Damage, Range) {
+    match kind {
+        TowerKind::Laser => (Damage(4), Range { squared: 15_000.0 }),
+        TowerKind::Sniper => (Damage(2), Range { squared: 90_000.0 }),
+        TowerKind::Cannon => (Damage(10), Range { squared: 4_900.0 }),
+        TowerKind::AntiAir => (Damage(6), Range { squared: 40_000.0 }),
+    }
+}
+
+type TowerBundle = (
+    Tower,
+    Position,
+    Health,
+    Damage,
+    Range,
+    Score,
+    Target,
+    CombatStats,
+    Targeting,
+    Level,
+    TowerKind,
+    Value,
+);
+
+fn tower_bundle(
+    kind: TowerKind,
+    position: Position,
+    (damage, range): (Damage, Range),
+) -> TowerBundle {
     let score = Score(0);
     let target = Target {
         position: None,
         entity: None,
     };
+    let health = Health {
+        value: kind.max_health(),
+        max: kind.max_health(),
+    };
+    (
+        Tower,
+        position,
+        health,
+        damage,
+        range,
+        score,
+        target,
+        CombatStats::default(),
+        Targeting::default(),
+        Level(1),
+        kind,
+        Value(kind.cost()),
+    )
+}
+
+pub fn batch_spawn_towers(world: &mut World, towers: usize) {
+    let mut rng = thread_rng();
+    let to_spawn = (0..towers)
+        .map(|_| {
+            let position = Position {
+                x: rng.gen_range(-100.0..100.0),
+                y: rng.gen_range(-100.0..100.0),
+            };
+            // the initial towers keep randomized stats
+            let stats = (
+                Damage(rng.gen_range(3..5)),
+                Range {
+                    squared: rng.gen_range(10_000.0..20_000.0),
+                },
+            );
+            tower_bundle(TowerKind::Laser, position, stats)
+        })
+        .collect::<Vec<TowerBundle>>();
+    world.spawn_batch(to_spawn);
+}
+
+// towers and ruins closer than PICK_RADIUS block building, so that clicks on a spot keep
+// picking a single entity. Ruins are rebuilt in place instead
+pub fn can_build(world: &World, position: &Vec2) -> bool {
+    let position = Position::from(*position);
+    let mut towers = world.query::<With<Tower, &Position>>();
+    let mut ruins = world.query::<With<Ruin, &Position>>();
+    closest(towers.iter().chain(ruins.iter()), &position, PICK_RADIUS).is_none()
+}
+
+pub fn spawn_tower(world: &mut World, position: &Vec2, kind: TowerKind) -> Entity {
+    let position = Position::from(*position);
+    world.spawn(tower_bundle(kind, position, tower_stats(kind)))
+}
+
+pub fn remove_tower(
+    world: &mut World,
+    position: &Vec2,
+) -> Option<(Entity, Position, i32, CombatStats)> {
+    let remove_position = Position::from(*position);
+    let closest_entity_to_position = closest(
+        world.query::<With<Tower, &Position>>().iter(),
+        &remove_position,
+        PICK_RADIUS,
+    );
+    closest_entity_to_position.and_then(|id| {
+        sell_tower(world, id).map(|(position, refund, stats)| (id, position, refund, stats))
+    })
+}
+
+pub fn refund(value: &Value) -> i32 {
+    value.0 / 2
+}
+
+// despawns the tower, returns its position, refund and final stats if `entity` was a tower
+pub fn sell_tower(world: &mut World, entity: Entity) -> Option<(Position, i32, CombatStats)> {
+    let sold = world
+        .query_one::<With<Tower, (&Position, &Value, &CombatStats)>>(entity)
+        .ok()
+        .and_then(|mut query| {
+            query
+                .get()
+                .map(|(position, value, stats)| (position.clone(), refund(value), stats.clone()))
+        })?;
+    world.despawn(entity).unwrap();
+    Some(sold)
+}
 
-    world.spawn((position, damage, range, score, target));
+pub fn upgrade_cost(kind: TowerKind, level: &Level) -> i32 {
+    kind.cost() * level.0 as i32
 }
 
-pub fn remove_tower(world: &mut World, position: &Vec2) {
-    let remove_position = Position {
-        x: position.x as i32,
-        y: position.y as i32,
+// returns the gold spent, None if `entity` is not a tower or the cost cannot be spent
+pub fn upgrade_tower(world: &mut World, entity: Entity, economy: &mut Economy) -> Option<i32> {
+    let (kind, damage, range, level, value) = world
+        .query_one_mut::<With<
+            Tower,
+            (&TowerKind, &mut Damage, &mut Range, &mut Level, &mut Value),
+        >>(entity)
+        .ok()?;
+    let cost = upgrade_cost(*kind, level);
+    if !economy.try_spend(cost) {
+        return None;
+    }
+    level.0 += 1;
+    damage.0 += 1;
+    // +10% range radius
+    range.squared *= 1.21;
+    value.0 += cost;
+    Some(cost)
+}
+
+pub fn repair_cost(health: &Health) -> i32 {
+    ((health.max - health.value) as f32 * REPAIR_COST_PER_HEALTH).ceil() as i32
+}
+
+// restores the tower to full health, returns the gold spent, None if `entity` is not a
+// damaged tower or the cost cannot be spent
+pub fn repair_tower(world: &mut World, entity: Entity, economy: &mut Economy) -> Option<i32> {
+    let mut query = world.query_one::<With<Tower, &mut Health>>(entity).ok()?;
+    let health = query.get()?;
+    let cost = repair_cost(health);
+    if health.value >= health.max || !economy.try_spend(cost) {
+        return None;
+    }
+    health.value = health.max;
+    Some(cost)
+}
+
+pub fn rebuild_cost(ruin: &Ruin) -> i32 {
+    ruin.kind.cost() / 2
+}
+
+// replaces the ruin with a new level 1 tower, returns the tower, its position and the gold
+// spent, None if `entity` is not a ruin or the cost cannot be spent
+pub fn rebuild_tower(
+    world: &mut World,
+    entity: Entity,
+    economy: &mut Economy,
+) -> Option<(Entity, Position, i32)> {
+    let (kind, position, cost) = {
+        let mut query = world.query_one::<(&Ruin, &Position)>(entity).ok()?;
+        let (ruin, position) = query.get()?;
+        (ruin.kind, position.clone(), rebuild_cost(ruin))
     };
-    let closest_entity_to_position = world
-        .query::<With<Damage, &Position>>()
-        .iter()
-        .filter(|(_id, p)| (*p - &remove_position).norm_squared() < 100i32)
-        .min_by_key(|(_id, p)| (*p - &remove_position).norm_squared())
-        .map(|(id, _p)| id);
-    if let Some(id) = closest_entity_to_position {
-        world.despawn(id).unwrap();
+    if !economy.try_spend(cost) {
+        return None;
+    }
+    world.despawn(entity).unwrap();
+    let tower = world.spawn(tower_bundle(kind, position.clone(), tower_stats(kind)));
+    Some((tower, position, cost))
+}
+
+pub fn set_targeting(world: &mut World, entity: Entity, targeting: Targeting) {
+    if let Ok(mut current) = world.get_mut::<Targeting>(entity) {
+        *current = targeting;
+    }
+}
+
+impl Selection {
+    // Entity handles carry a generation, so a despawned entity never matches
+    // a new entity reusing its id
+    pub fn update(&mut self, world: &World, step: usize) {
+        if let SelectionStatus::Alive = self.status {
+            if world.contains(self.entity) {
+                *self = get_selection(world, self.entity);
+            } else {
+                self.status = SelectionStatus::Despawned { step };
+            }
+        }
+    }
+
+    pub fn on_event(&mut self, event: &Event, step: usize) {
+        if let SelectionStatus::Alive = self.status {
+            match event {
+                Event::UnitKilled { unit, source, .. } if *unit == self.entity => {
+                    self.status = SelectionStatus::Killed {
+                        step,
+                        source: *source,
+                    };
+                }
+                Event::UnitLeaked { unit } if *unit == self.entity => {
+                    self.status = SelectionStatus::Leaked { step };
+                }
+                Event::TowerSold { tower, .. } if *tower == self.entity => {
+                    self.status = SelectionStatus::Sold { step };
+                }
+                Event::TowerDestroyed { tower, unit, .. } if *tower == self.entity => {
+                    self.status = SelectionStatus::Destroyed { step, unit: *unit };
+                }
+                Event::HeroKilled { hero, unit, .. } if *hero == self.entity => {
+                    self.status = SelectionStatus::HeroKilled { step, unit: *unit };
+                }
+                _ => {}
+            }
+        }
     }
 }
 
-pub fn get_selection(world: &mut World, entity: Entity) -> Selection {
-    let damage = world.get_mut::<Damage>(entity).ok();
-    let health = world.get_mut::<Health>(entity).ok();
-    let range = world.get_mut::<Range>(entity).ok();
-    let speed = world.get_mut::<Speed>(entity).ok();
-    let position = world.get_mut::<Position>(entity).ok();
-    let score = world.get_mut::<Score>(entity).ok();
+// None for entities without a marker, they cannot be selected, nor can projectiles
+pub fn entity_kind(world: &World, entity: Entity) -> Option<EntityKind> {
+    world.entity(entity).ok().and_then(EntityKind::of)
+}
+
+pub fn get_selection(world: &World, entity: Entity) -> Selection {
+    let damage = world.get::<Damage>(entity).ok();
+    let health = world.get::<Health>(entity).ok();
+    let range = world.get::<Range>(entity).ok();
+    let speed = world.get::<Speed>(entity).ok();
+    let position = world.get::<Position>(entity).ok();
+    let score = world.get::<Score>(entity).ok();
+    let targeting = world.get::<Targeting>(entity).ok();
+    let level = world.get::<Level>(entity).ok();
+    let kind = world.get::<TowerKind>(entity).ok();
+    let value = world.get::<Value>(entity).ok();
+    let progress = world.get::<PathProgress>(entity).ok();
+    let movement = world.get::<Movement>(entity).ok();
+    let ruin = world.get::<Ruin>(entity).ok();
     Selection {
         entity,
-        position: position.map(|p| p.clone()),
-        range: range.map(|r| r.clone()),
-        damage: damage.map(|d| d.clone()),
-        speed: speed.map(|s| s.clone()),
-        health: health.map(|h| h.clone()),
-        score: score.map(|s| s.clone()),
+        status: SelectionStatus::Alive,
+        entity_kind: entity_kind(world, entity),
+        position: position.map(|p| p.deref().clone()),
+        range: range.map(|r| r.deref().clone()),
+        damage: damage.map(|d| d.deref().clone()),
+        speed: speed.map(|s| s.deref().clone()),
+        health: health.map(|h| h.deref().clone()),
+        score: score.map(|s| s.deref().clone()),
+        targeting: targeting.map(|t| *t),
+        level: level.map(|l| l.deref().clone()),
+        kind: kind.map(|k| *k),
+        value: value.map(|v| v.deref().clone()),
+        progress: progress.map(|p| p.deref().clone()),
+        movement: movement.map(|m| *m),
+        ruin: ruin.map(|r| r.deref().clone()),
     }
 }
 
-pub fn closest_entity(world: &mut World, position: &Vec2) -> Option<Selection> {
-    let target = Position {
-        x: position.x as i32,
-        y: position.y as i32,
-    };
-    let closest_entity_to_position = world
-        .query::<&Position>()
+pub fn closest_entity(world: &World, position: &Vec2) -> Option<Selection> {
+    let target = Position::from(*position);
+    let mut query = world.query::<&Position>();
+    let candidates = query
         .iter()
-        .filter(|(_id, p)| (*p - &target).norm_squared() < 100i32)
-        .min_by_key(|(_id, p)| (*p - &target).norm_squared())
-        .map(|(id, _p)| id);
+        .filter(|(id, _)| entity_kind(world, *id).is_some_and(EntityKind::is_selectable));
+    let closest_entity_to_position = closest(candidates, &target, PICK_RADIUS);
     closest_entity_to_position.map(|id| get_selection(world, id))
 }
+
+pub fn entities_in_box(world: &World, corner: &Vec2, opposite_corner: &Vec2) -> Vec<Selection> {
+    let min = corner.min(*opposite_corner);
+    let max = corner.max(*opposite_corner);
+    let inside: Vec<Entity> = world
+        .query::<&Position>()
+        .iter()
+        .filter(|(id, _)| entity_kind(world, *id).is_some_and(EntityKind::is_selectable))
+        .filter(|(_id, p)| {
+            let (x, y) = (p.x, p.y);
+            min.x <= x && x <= max.x && min.y <= y && y <= max.y
+        })
+        .map(|(id, _p)| id)
+        .collect();
+    inside
+        .into_iter()
+        .map(|id| get_selection(world, id))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::Projectile;
+    use crate::config::GameplayConfig;
+    use crate::map::default_map;
+    use macroquad::prelude::vec2;
+
+    fn unit(world: &mut World, x: f32, y: f32) -> Entity {
+        let health = Health { value: 10, max: 10 };
+        world.spawn((Unit, Position { x, y }, health))
+    }
+
+    fn economy(gold: i32) -> Economy {
+        Economy::new(&GameplayConfig {
+            gold,
+            ..GameplayConfig::default()
+        })
+    }
+
+    #[test]
+    fn alive_selections_follow_their_entity() {
+        let mut world = World::new();
+        let unit = unit(&mut world, 1.0, 2.0);
+        let mut selection = get_selection(&world, unit);
+        world.get_mut::<Health>(unit).unwrap().value = 3;
+        selection.update(&world, 5);
+        assert!(matches!(selection.status, SelectionStatus::Alive));
+        assert_eq!(selection.health.map(|h| h.value), Some(3));
+    }
+
+    #[test]
+    fn killed_selections_keep_their_last_known_components() {
+        let mut world = World::new();
+        let unit = unit(&mut world, 1.0, 2.0);
+        let tower = world.spawn(());
+        let mut selection = get_selection(&world, unit);
+        let killed = Event::UnitKilled {
+            unit,
+            source: DamageSource::Tower(tower),
+            position: Position { x: 1.0, y: 2.0 },
+        };
+        selection.on_event(&killed, 7);
+        world.despawn(unit).unwrap();
+        selection.update(&world, 8);
+        // later events do not override what happened first
+        selection.on_event(&Event::UnitLeaked { unit }, 9);
+        assert!(matches!(
+            selection.status,
+            SelectionStatus::Killed { step: 7, source } if source == DamageSource::Tower(tower)
+        ));
+        assert_eq!(selection.position, Some(Position { x: 1.0, y: 2.0 }));
+    }
+
+    #[test]
+    fn events_of_other_entities_are_ignored() {
+        let mut world = World::new();
+        let unit = unit(&mut world, 0.0, 0.0);
+        let other = world.spawn(());
+        let mut selection = get_selection(&world, unit);
+        selection.on_event(&Event::UnitLeaked { unit: other }, 1);
+        let sold = Event::TowerSold {
+            tower: other,
+            position: Position { x: 0.0, y: 0.0 },
+            refund: 25,
+            stats: CombatStats::default(),
+        };
+        selection.on_event(&sold, 1);
+        assert!(matches!(selection.status, SelectionStatus::Alive));
+    }
+
+    #[test]
+    fn despawns_without_an_event_are_detected() {
+        let mut world = World::new();
+        let unit = unit(&mut world, 0.0, 0.0);
+        let mut selection = get_selection(&world, unit);
+        world.despawn(unit).unwrap();
+        // the new entity reuses the slot with another generation
+        let reused = world.spawn((Position { x: 50.0, y: 50.0 },));
+        assert_eq!(reused.id(), unit.id());
+        selection.update(&world, 4);
+        assert!(matches!(
+            selection.status,
+            SelectionStatus::Despawned { step: 4 }
+        ));
+        assert_eq!(selection.position, Some(Position { x: 0.0, y: 0.0 }));
+    }
+
+    #[test]
+    fn closest_entity_picks_within_the_pick_radius() {
+        let mut world = World::new();
+        let near = unit(&mut world, 3.0, 0.0);
+        unit(&mut world, 6.0, 0.0);
+        // entities without a marker cannot be selected
+        world.spawn((Position { x: 1.0, y: 0.0 },));
+        let picked = closest_entity(&world, &vec2(1.0, 0.0)).map(|s| s.entity);
+        assert_eq!(picked, Some(near));
+        assert!(closest_entity(&world, &vec2(30.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn box_selection_picks_entities_inside_any_corner_order() {
+        let mut world = World::new();
+        let inside = unit(&mut world, 5.0, 5.0);
+        let edge = unit(&mut world, 10.0, 0.0);
+        unit(&mut world, 11.0, 5.0);
+        let mut picked: Vec<Entity> = entities_in_box(&world, &vec2(10.0, 10.0), &vec2(0.0, 0.0))
+            .iter()
+            .map(|s| s.entity)
+            .collect();
+        picked.sort();
+        let mut expected = vec![inside, edge];
+        expected.sort();
+        assert_eq!(picked, expected);
+    }
+
+    #[test]
+    fn costs() {
+        assert_eq!(refund(&Value(81)), 40);
+        assert_eq!(upgrade_cost(TowerKind::Cannon, &Level(3)), 450);
+        let health = |value| Health { value, max: 100 };
+        assert_eq!(repair_cost(&health(100)), 0);
+        assert_eq!(repair_cost(&health(99)), 1);
+        assert_eq!(repair_cost(&health(60)), 10);
+        assert_eq!(repair_cost(&health(-3)), 26);
+        let ruin = Ruin {
+            kind: TowerKind::Cannon,
+        };
+        assert_eq!(rebuild_cost(&ruin), 75);
+    }
+
+    #[test]
+    fn towers_are_upgraded_repaired_and_sold_for_gold() {
+        let mut world = World::new();
+        let tower = spawn_tower(&mut world, &vec2(0.0, 0.0), TowerKind::Sniper);
+        let mut poor = economy(80 - 1);
+        assert_eq!(upgrade_tower(&mut world, tower, &mut poor), None);
+        assert_eq!(poor.gold, 80 - 1);
+        let mut economy = economy(100);
+        assert_eq!(upgrade_tower(&mut world, tower, &mut economy), Some(80));
+        assert_eq!(economy.gold, 20);
+        assert_eq!(world.get::<Level>(tower).unwrap().0, 2);
+        assert_eq!(world.get::<Value>(tower).unwrap().0, 2 * 80);
+
+        assert_eq!(repair_tower(&mut world, tower, &mut economy), None);
+        world.get_mut::<Health>(tower).unwrap().value -= 40;
+        economy.gold = 9;
+        assert_eq!(repair_tower(&mut world, tower, &mut economy), None);
+        assert_eq!(economy.gold, 9);
+        economy.gold = 10;
+        assert_eq!(repair_tower(&mut world, tower, &mut economy), Some(10));
+        assert_eq!(economy.gold, 0);
+        assert_eq!(world.get::<Health>(tower).unwrap().value, 60);
+
+        let (_, refund, _) = sell_tower(&mut world, tower).unwrap();
+        assert_eq!(refund, 80);
+        assert!(!world.contains(tower));
+        assert!(sell_tower(&mut world, tower).is_none());
+    }
+
+    #[test]
+    fn ruins_are_rebuilt_in_place_and_block_building() {
+        let mut world = World::new();
+        let ruin = world.spawn((
+            Position { x: 100.0, y: 0.0 },
+            Ruin {
+                kind: TowerKind::Sniper,
+            },
+        ));
+        assert!(!can_build(&world, &vec2(105.0, 0.0)));
+        assert!(can_build(&world, &vec2(110.0, 0.0)));
+        let mut economy = economy(39);
+        assert_eq!(rebuild_tower(&mut world, ruin, &mut economy), None);
+        economy.gold = 40;
+        let (tower, position, cost) = rebuild_tower(&mut world, ruin, &mut economy).unwrap();
+        assert_eq!(economy.gold, 0);
+        assert_eq!((position, cost), (Position { x: 100.0, y: 0.0 }, 40));
+        assert!(!world.contains(ruin));
+        assert_eq!(*world.get::<TowerKind>(tower).unwrap(), TowerKind::Sniper);
+        assert_eq!(world.get::<Level>(tower).unwrap().0, 1);
+        economy.gold = 100;
+        assert!(rebuild_tower(&mut world, tower, &mut economy).is_none());
+        assert_eq!(economy.gold, 100);
+        assert!(!can_build(&world, &vec2(100.0, 5.0)));
+        // units do not block building
+        unit(&mut world, 0.0, 0.0);
+        assert!(can_build(&world, &vec2(0.0, 0.0)));
+    }
+
+    #[test]
+    fn entity_kinds_follow_the_markers() {
+        let mut world = World::new();
+        let unit = unit(&mut world, 0.0, 0.0);
+        let tower = spawn_tower(&mut world, &vec2(0.0, 0.0), TowerKind::Laser);
+        let ruin = world.spawn((Ruin {
+            kind: TowerKind::Laser,
+        },));
+        let other = world.spawn((Health { value: 1, max: 1 },));
+        assert_eq!(entity_kind(&world, unit), Some(EntityKind::Unit));
+        assert_eq!(entity_kind(&world, tower), Some(EntityKind::Tower));
+        assert_eq!(entity_kind(&world, ruin), Some(EntityKind::Ruin));
+        assert_eq!(entity_kind(&world, other), None);
+    }
+
+    #[test]
+    fn projectiles_cannot_be_selected() {
+        let mut world = World::new();
+        let projectile = world.spawn((Projectile, Position { x: 0.0, y: 0.0 }));
+        assert_eq!(
+            entity_kind(&world, projectile),
+            Some(EntityKind::Projectile)
+        );
+        assert!(closest_entity(&world, &vec2(0.0, 0.0)).is_none());
+        assert!(entities_in_box(&world, &vec2(-5.0, -5.0), &vec2(5.0, 5.0)).is_empty());
+        let unit = unit(&mut world, 1.0, 0.0);
+        let picked = closest_entity(&world, &vec2(0.0, 0.0)).unwrap();
+        assert_eq!(picked.entity, unit);
+    }
+
+    #[test]
+    fn siege_units_spawn_ready_to_attack() {
+        let mut world = World::new();
+        batch_spawn_units(&mut world, 200, &default_map());
+        let mut query = world.query::<With<Unit, (&Movement, Option<&Damage>)>>();
+        for (_, (movement, damage)) in query.iter() {
+            if damage.is_some() {
+                assert_eq!(*movement, Movement::Ground);
+            }
+        }
+        let siege = world
+            .query::<With<Unit, (&Damage, &Range, &Target)>>()
+            .iter()
+            .count();
+        assert!(siege > 0);
+    }
+}
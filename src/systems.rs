@@ -1,39 +1,151 @@
 use crate::components::{
-    Damage, Distance, Health, Position, Range, Score, Speed, Target, Waypoint,
+    CombatStats, Damage, Destination, Distance, Health, Hero, Movement, PathProgress, Position,
+    Range, Ruin, Score, Speed, Stunned, Target, Targeting, Tower, TowerKind, Unit, Waypoint,
 };
+use crate::events::{DamageSource, Event, Events};
+use crate::geometry::{closest, distance, distance_squared};
+use crate::map::Map;
+use rand::thread_rng;
+use std::collections::HashMap;
 use std::ops::Deref;
 
 use hecs::{Entity, PreparedQuery, With, World};
 
-fn direction(movement: Distance) -> Distance {
-    movement.clamp(-1, 1)
-}
-
-pub fn system_integrate_motion(
-    world: &mut World,
-    query: &mut PreparedQuery<(&mut Position, &mut Waypoint, &Speed)>,
-    waypoints: &[Position],
-) {
-    for (_id, (pos, waypoint, spd)) in query.query_mut(world) {
-        if let Some(target) = waypoints.get(waypoint.index) {
-            let dx: i32 = target.x - pos.x;
-            let dy: i32 = target.y - pos.y;
-            let dx = direction(dx) * dx.abs().min(spd.0);
-            let dy = direction(dy) * dy.abs().min(spd.0);
-            pos.x += dx;
-            pos.y += dy;
-            if pos == target {
-                waypoint.index += 1;
+// units closer than this to their waypoint head to the next one
+const ARRIVAL_RADIUS: Distance = 1.0;
+
+pub type MotionQuery = PreparedQuery<
+    With<
+        Unit,
+        (
+            &'static mut Position,
+            &'static mut Waypoint,
+            &'static Speed,
+            &'static mut PathProgress,
+            Option<&'static Target>,
+            Option<&'static Stunned>,
+        ),
+    >,
+>;
+
+pub fn system_integrate_motion(world: &mut World, query: &mut MotionQuery, map: &Map) {
+    let mut rng = thread_rng();
+    for (_id, (pos, waypoint, spd, progress, target, stunned)) in query.query_mut(world) {
+        // siege units stand still while attacking a tower
+        if stunned.is_some() || target.is_some_and(|target| target.entity.is_some()) {
+            continue;
+        }
+        // units move in a straight line toward their waypoint, the movement left
+        // when reaching it carries over toward the next one
+        let mut movement = spd.0;
+        while movement > 0.0 {
+            let target = match map.position(waypoint.node) {
+                Some(target) => target,
+                None => break,
+            };
+            let delta = target - pos;
+            let distance = distance(target, pos);
+            if distance <= movement || distance <= ARRIVAL_RADIUS {
+                // close enough, pushed units are not pulled back onto the waypoint
+                if distance <= movement {
+                    *pos = target.clone();
+                    movement -= distance;
+                }
+                // goals have no next node, the unit stays there until removed
+                match map.choose_next(waypoint.node, &mut rng) {
+                    Some(next) => waypoint.node = next,
+                    None => break,
+                }
+            } else {
+                pos.x += delta.x * movement / distance;
+                pos.y += delta.y * movement / distance;
+                movement = 0.0;
             }
         }
+        progress.traveled += spd.0 - movement;
+        progress.remaining = map.shortest_remaining_distance(pos, waypoint.node);
     }
 }
 
-pub fn system_remove_arrived(world: &mut World, target: &Position) -> usize {
+// units closer than this push each other apart
+const SEPARATION_RADIUS: Distance = 12.0;
+// fraction of the overlap resolved per step
+const SEPARATION_STRENGTH: Distance = 0.25;
+// cap on the push per step so that separation never outruns motion
+const MAX_SEPARATION_STEP: Distance = 1.0;
+
+fn grid_cell(position: &Position) -> (i64, i64) {
+    (
+        (position.x / SEPARATION_RADIUS).floor() as i64,
+        (position.y / SEPARATION_RADIUS).floor() as i64,
+    )
+}
+
+// Spreads units stacked on the same lane, neighbours are looked up in a grid of
+// SEPARATION_RADIUS cells. Runs before motion: units reaching a node are snapped onto it
+// by the motion system, so arrival detection is unaffected
+pub fn system_separate_units(world: &mut World) {
+    let units: Vec<(Entity, Position)> = world
+        .query::<With<Unit, &Position>>()
+        .iter()
+        .map(|(id, position)| (id, position.clone()))
+        .collect();
+    let mut grid: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+    for (i, (_, position)) in units.iter().enumerate() {
+        grid.entry(grid_cell(position)).or_default().push(i);
+    }
+    let mut pushes = vec![Position { x: 0.0, y: 0.0 }; units.len()];
+    for (i, (_, position)) in units.iter().enumerate() {
+        let (cx, cy) = grid_cell(position);
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for &j in grid.get(&(cx + dx, cy + dy)).into_iter().flatten() {
+                    if j == i {
+                        continue;
+                    }
+                    let away = position - &units[j].1;
+                    let distance = distance(position, &units[j].1);
+                    if distance >= SEPARATION_RADIUS {
+                        continue;
+                    }
+                    let push = (SEPARATION_RADIUS - distance) * SEPARATION_STRENGTH * 0.5;
+                    let (ux, uy) = if distance > 0.0 {
+                        (away.x / distance, away.y / distance)
+                    } else {
+                        // units on the exact same spot split along a direction derived
+                        // from the pair, the lower index is pushed one way and the higher
+                        // one the opposite way
+                        let (low, high) = (i.min(j), i.max(j));
+                        let angle =
+                            ((low * 7919 + high) % 360) as f32 * std::f32::consts::PI / 180.0;
+                        let sign = if i == low { 1.0 } else { -1.0 };
+                        (sign * angle.cos(), sign * angle.sin())
+                    };
+                    pushes[i].x += ux * push;
+                    pushes[i].y += uy * push;
+                }
+            }
+        }
+    }
+    for ((id, _), push) in units.iter().zip(pushes) {
+        let length = push.norm();
+        if length == 0.0 {
+            continue;
+        }
+        let scale = length.min(MAX_SEPARATION_STEP) / length;
+        if let Ok(mut position) = world.get_mut::<Position>(*id) {
+            position.x += push.x * scale;
+            position.y += push.y * scale;
+        }
+    }
+}
+
+pub fn system_remove_arrived(world: &mut World, map: &Map, events: &mut Events) -> usize {
     let mut to_remove: Vec<Entity> = Vec::new();
-    for (id, pos) in &mut world.query::<With<Health, &Position>>() {
-        if pos == target {
+    for (id, pos) in &mut world.query::<With<Unit, &Position>>() {
+        if map.in_goal_zone(pos) {
             debug!("ID: {:?} has reached its target.", id);
+            events.push(Event::UnitLeaked { unit: id });
             to_remove.push(id);
         }
     }
@@ -45,84 +157,361 @@ pub fn system_remove_arrived(world: &mut World, target: &Position) -> usize {
     removed
 }
 
+// destroyed towers leave a ruin behind
 pub fn system_remove_dead(world: &mut World) -> usize {
-    let mut to_remove: Vec<Entity> = Vec::new();
-    for (id, health) in &mut world.query::<&Health>() {
+    let mut to_remove: Vec<(Entity, Option<(TowerKind, Position)>)> = Vec::new();
+    for (id, (health, position, kind)) in
+        &mut world.query::<(&Health, &Position, Option<With<Tower, &TowerKind>>)>()
+    {
         if health.value <= 0 {
             debug!("ID: {:?} health is <= 0", id);
-            to_remove.push(id);
+            to_remove.push((id, kind.map(|kind| (*kind, position.clone()))));
         }
     }
 
     let removed = to_remove.len();
-    for entity in to_remove {
+    for (entity, ruin) in to_remove {
         world.despawn(entity).unwrap();
+        if let Some((kind, position)) = ruin {
+            world.spawn((position, Ruin { kind }));
+        }
     }
     removed
 }
 
+// The hero walks straight to the position it was ordered to, ignoring the lanes
+pub fn system_move_heroes(world: &mut World) {
+    let mut arrived: Vec<Entity> = Vec::new();
+    for (id, (position, speed, destination)) in
+        world.query_mut::<With<Hero, (&mut Position, &Speed, &Destination)>>()
+    {
+        let delta = &destination.0 - position;
+        let distance = distance(&destination.0, position);
+        if distance <= speed.0 {
+            *position = destination.0.clone();
+            arrived.push(id);
+        } else {
+            position.x += delta.x * speed.0 / distance;
+            position.y += delta.y * speed.0 / distance;
+        }
+    }
+    for entity in arrived {
+        world.remove_one::<Destination>(entity).unwrap();
+    }
+}
+
+// The hero fights like a tower targeting the closest unit, its damage goes through the
+// same pipeline so that its kills are reported like tower kills
+pub fn system_hero_attack(world: &mut World, events: &mut Events) {
+    for (hero_id, (position, health, damage, range, target)) in
+        &mut world.query::<With<Hero, (&Position, &Health, &Damage, &Range, &mut Target)>>()
+    {
+        let chosen = if health.value > 0 {
+            choose_target(
+                world,
+                position,
+                range,
+                Targeting::Closest,
+                target.entity,
+                |_| true,
+            )
+        } else {
+            None
+        };
+        let source = DamageSource::Hero(hero_id);
+        match chosen.and_then(|e| apply_damage(world, e, damage.0, source, events).map(|h| (e, h)))
+        {
+            Some((entity, hit)) => {
+                target.position = Some(hit.position);
+                target.entity = Some(entity);
+            }
+            None => {
+                target.position = None;
+                target.entity = None;
+            }
+        }
+    }
+}
+
+// counts stuns down and lifts the expired ones
+pub fn system_update_stuns(world: &mut World) {
+    let mut expired: Vec<Entity> = Vec::new();
+    for (id, stunned) in world.query_mut::<&mut Stunned>() {
+        stunned.steps = stunned.steps.saturating_sub(1);
+        if stunned.steps == 0 {
+            expired.push(id);
+        }
+    }
+    for entity in expired {
+        world.remove_one::<Stunned>(entity).unwrap();
+    }
+}
+
 pub fn system_score(world: &World) -> usize {
     world
-        .query::<&Score>()
+        .query::<With<Tower, &Score>>()
         .iter()
         .map(|(_id, score)| score.0)
         .sum::<i32>() as usize
 }
 
 pub fn system_units_left(world: &World) -> usize {
-    world.query::<&Health>().iter().count()
+    world.query::<With<Unit, &Health>>().iter().count()
 }
 
-// In this system entities find the closest entity and fire at them
-pub fn system_fire_at_closest(world: &mut World) {
-    for (tower_id, (tower_position, tower_damage, tower_range, tower_score, tower_target)) in
-        &mut world.query::<With<Damage, (&Position, &Damage, &Range, &mut Score, &mut Target)>>()
+// Siege units stop to attack the closest tower or hero in their range until it is destroyed
+pub fn system_units_attack_towers(world: &mut World, events: &mut Events) {
+    for (unit_id, (unit_position, unit_damage, unit_range, unit_target, stunned)) in &mut world
+        .query::<With<Unit, (&Position, &Damage, &Range, &mut Target, Option<&Stunned>)>>()
     {
-        let mut closest: Option<Entity> = None;
+        if stunned.is_some() {
+            unit_target.position = None;
+            unit_target.entity = None;
+            continue;
+        }
+        let chosen = {
+            let alive_in_range = |(_, (position, health)): &(Entity, (&Position, &Health))| {
+                health.value > 0 && unit_range.contains(unit_position, position)
+            };
+            let mut towers = world.query::<With<Tower, (&Position, &Health)>>();
+            let mut heroes = world.query::<With<Hero, (&Position, &Health)>>();
+            let in_range = towers
+                .iter()
+                .filter(alive_in_range)
+                .map(|(id, (position, _))| ((id, false), position))
+                .chain(
+                    heroes
+                        .iter()
+                        .filter(alive_in_range)
+                        .map(|(id, (position, _))| ((id, true), position)),
+                );
+            closest(in_range, unit_position, Distance::MAX)
+        };
+        match chosen {
+            Some((target, is_hero)) => {
+                let mut target_health = world.get_mut::<Health>(target).unwrap();
+                let target_position = world.get::<Position>(target).unwrap().deref().clone();
+                target_health.value -= unit_damage.0;
+                let destroyed = target_health.value <= 0;
+                if is_hero {
+                    events.push(Event::HeroHit {
+                        hero: target,
+                        unit: unit_id,
+                        damage: unit_damage.0,
+                        position: target_position.clone(),
+                    });
+                    if destroyed {
+                        debug!("Hero {:?} was killed by unit {:?}!", target, unit_id);
+                        events.push(Event::HeroKilled {
+                            hero: target,
+                            unit: unit_id,
+                            position: target_position.clone(),
+                        });
+                    }
+                } else {
+                    events.push(Event::TowerHit {
+                        tower: target,
+                        unit: unit_id,
+                        damage: unit_damage.0,
+                        position: target_position.clone(),
+                    });
+                    if destroyed {
+                        debug!("Tower {:?} was destroyed by unit {:?}!", target, unit_id);
+                        let stats = world
+                            .get::<CombatStats>(target)
+                            .map(|stats| stats.deref().clone())
+                            .unwrap_or_default();
+                        events.push(Event::TowerDestroyed {
+                            tower: target,
+                            unit: unit_id,
+                            position: target_position.clone(),
+                            stats,
+                        });
+                    }
+                }
+                unit_target.position = Some(target_position);
+                unit_target.entity = Some(target);
+            }
+            None => {
+                unit_target.position = None;
+                unit_target.entity = None;
+            }
+        }
+    }
+}
+
+// Outcome of damage dealt to a unit
+pub struct Hit {
+    pub dealt: i32,
+    pub overkill: i32,
+    pub killed: bool,
+    pub position: Position,
+}
 
-        if let Some(entity) = tower_target.entity {
+// Damage pipeline shared by towers and abilities: lowers the unit health and reports the
+// hit and the kill. None when the unit is already dead or does not exist
+pub fn apply_damage(
+    world: &World,
+    unit: Entity,
+    damage: i32,
+    source: DamageSource,
+    events: &mut Events,
+) -> Option<Hit> {
+    let mut health = world.get_mut::<Health>(unit).ok()?;
+    let position = world.get::<Position>(unit).ok()?.deref().clone();
+    if health.value <= 0 {
+        return None;
+    }
+    let hit = Hit {
+        dealt: damage.min(health.value),
+        overkill: (damage - health.value).max(0),
+        killed: health.value <= damage,
+        position: position.clone(),
+    };
+    health.value -= damage;
+    debug!(
+        "Unit {:?} was damaged by {} for {:?} HP",
+        unit, source, damage
+    );
+    match source {
+        DamageSource::Tower(tower) => events.push(Event::TowerFired {
+            tower,
+            target: unit,
+            damage,
+            position: position.clone(),
+        }),
+        DamageSource::Ability(ability) => events.push(Event::AbilityHit {
+            ability,
+            unit,
+            damage,
+            position: position.clone(),
+        }),
+        DamageSource::Hero(hero) => events.push(Event::HeroFired {
+            hero,
+            target: unit,
+            damage,
+            position: position.clone(),
+        }),
+    }
+    if hit.killed {
+        debug!("Unit {:?} was killed by {}!", unit, source);
+        events.push(Event::UnitKilled {
+            unit,
+            source,
+            position,
+        });
+    }
+    Some(hit)
+}
+
+// Unit in range of a shooter picked according to its targeting, units whose movement
+// `can_target` rejects are ignored. Shooters targeting the closest unit keep firing at
+// their current target while it is in range
+fn choose_target(
+    world: &World,
+    position: &Position,
+    range: &Range,
+    targeting: Targeting,
+    current: Option<Entity>,
+    can_target: impl Fn(Movement) -> bool,
+) -> Option<Entity> {
+    if targeting == Targeting::Closest {
+        if let Some(entity) = current {
+            let alive = world
+                .get::<Health>(entity)
+                .is_ok_and(|health| health.value > 0);
             if let Ok(target_position) = world.get::<Position>(entity) {
-                if (target_position.deref() - tower_position).norm_squared() <= tower_range.squared
-                {
-                    closest = Some(entity);
+                if alive && range.contains(position, target_position.deref()) {
+                    return Some(entity);
                 }
             }
         }
-        if closest.is_none() {
-            closest = world
-                .query::<With<Health, &Position>>()
-                .iter()
-                .filter(|(target_id, target_position)| {
-                    *target_id != tower_id
-                        && (*target_position - tower_position).norm_squared() <= tower_range.squared
-                })
-                .min_by_key(|(_, target_position)| {
-                    (tower_position - *target_position).norm_squared()
-                })
-                .map(|(entity, _pos)| entity);
-        }
-
-        match closest {
-            Some(entity) => {
-                let mut target_health = world.get_mut::<Health>(entity).unwrap();
-                let target_position = world.get::<Position>(entity).unwrap();
-
-                if target_health.value > 0 {
-                    target_health.value -= tower_damage.0;
-                    debug!(
-                        "Unit {:?} was damaged by {:?} for {:?} HP",
-                        closest, tower_id, tower_damage.0
-                    );
-                    if target_health.value <= 0 {
-                        tower_score.0 += 1;
-                        debug!("Unit {:?} was killed by tower {:?}!", entity, tower_id);
-                    }
-                    tower_target.position = Some(Position {
-                        x: target_position.x,
-                        y: target_position.y,
-                    });
-                    tower_target.entity = Some(entity);
+    }
+    let mut query =
+        world.query::<With<Unit, (&Position, &Health, Option<&PathProgress>, Option<&Movement>)>>();
+    let in_range = query
+        .iter()
+        .filter(|(_, (_, health, _, movement))| {
+            health.value > 0 && can_target(movement.copied().unwrap_or_default())
+        })
+        .map(|(id, (position, health, progress, _))| (id, (position, health, progress)))
+        .filter(|(_, (target_position, _, _))| range.contains(position, target_position));
+    // units without progress are considered as far as possible from the end
+    let remaining = |progress: &Option<&PathProgress>| {
+        progress.map_or(Distance::MAX, |progress| progress.remaining)
+    };
+    match targeting {
+        Targeting::Closest => in_range.min_by(|(_, (a, _, _)), (_, (b, _, _))| {
+            distance_squared(position, a).total_cmp(&distance_squared(position, b))
+        }),
+        Targeting::Strongest => in_range.max_by_key(|(_, (_, health, _))| health.value),
+        Targeting::Weakest => in_range.min_by_key(|(_, (_, health, _))| health.value),
+        Targeting::First => {
+            in_range.min_by(|(_, (_, _, a)), (_, (_, _, b))| remaining(a).total_cmp(&remaining(b)))
+        }
+        Targeting::Last => {
+            in_range.max_by(|(_, (_, _, a)), (_, (_, _, b))| remaining(a).total_cmp(&remaining(b)))
+        }
+    }
+    .map(|(entity, _)| entity)
+}
+
+// In this system towers pick a unit in range according to their targeting and fire at it
+pub fn system_fire_at_closest(world: &mut World, events: &mut Events) {
+    for (
+        tower_id,
+        (
+            tower_position,
+            tower_damage,
+            tower_range,
+            tower_score,
+            tower_target,
+            tower_stats,
+            tower_targeting,
+            tower_kind,
+        ),
+    ) in &mut world.query::<With<
+        Tower,
+        (
+            &Position,
+            &Damage,
+            &Range,
+            &mut Score,
+            &mut Target,
+            &mut CombatStats,
+            &Targeting,
+            &TowerKind,
+        ),
+    >>() {
+        let chosen = choose_target(
+            world,
+            tower_position,
+            tower_range,
+            *tower_targeting,
+            tower_target.entity,
+            |movement| tower_kind.can_target(movement),
+        );
+
+        // uptime counts every step with a target, whether the shot lands or not
+        if chosen.is_some() {
+            tower_stats.steps_with_target += 1;
+        }
+        // the target is dropped when the shot does not land
+        let source = DamageSource::Tower(tower_id);
+        let landed = chosen.and_then(|entity| {
+            apply_damage(world, entity, tower_damage.0, source, events).map(|hit| (entity, hit))
+        });
+        match landed {
+            Some((entity, hit)) => {
+                tower_stats.shots_fired += 1;
+                tower_stats.damage_dealt += hit.dealt;
+                tower_stats.overkill += hit.overkill;
+                if hit.killed {
+                    tower_score.0 += 1;
+                    tower_stats.kills += 1;
                 }
+                tower_target.position = Some(hit.position);
+                tower_target.entity = Some(entity);
             }
             None => {
                 tower_target.position = None;
@@ -131,3 +520,551 @@ pub fn system_fire_at_closest(world: &mut World) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::abilities::Ability;
+    use crate::components::Distance;
+    use crate::map::{Branch, Curve, GoalZone, Map, Node, NodeId};
+
+    fn node(x: Distance, y: Distance, next: Option<NodeId>) -> Node {
+        Node {
+            position: Position { x, y },
+            branches: next
+                .map(|to| Branch {
+                    to,
+                    weight: 1,
+                    curve: Curve::Straight,
+                })
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    // map of a single lane starting at the first node, with a goal zone around the last one
+    fn lane(nodes: Vec<Node>) -> Map {
+        let zone = GoalZone {
+            center: nodes.last().unwrap().position.clone(),
+            radius: 2.0,
+        };
+        Map::new(nodes, vec![0], vec![zone]).unwrap()
+    }
+
+    fn unit(world: &mut World, x: Distance, y: Distance, health: i32) -> Entity {
+        let health = Health {
+            value: health,
+            max: health,
+        };
+        world.spawn((Unit, Position { x, y }, health))
+    }
+
+    fn tower(world: &mut World, x: Distance, y: Distance, damage: i32) -> Entity {
+        let target = Target {
+            position: None,
+            entity: None,
+        };
+        world.spawn((
+            Tower,
+            Position { x, y },
+            Damage(damage),
+            Range { squared: 100.0 },
+            Score(0),
+            target,
+            CombatStats::default(),
+            Targeting::default(),
+            TowerKind::Laser,
+        ))
+    }
+
+    #[test]
+    fn units_in_a_goal_zone_leak() {
+        let mut world = World::new();
+        let mut events = Events::default();
+        let map = lane(vec![node(0.0, 10.0, Some(1)), node(10.0, 10.0, None)]);
+        let arrived = unit(&mut world, 9.0, 11.0, 50);
+        let walking = unit(&mut world, 0.0, 10.0, 50);
+        for unit in [arrived, walking] {
+            world.insert_one(unit, Waypoint { node: 1 }).unwrap();
+        }
+        let removed = system_remove_arrived(&mut world, &map, &mut events);
+        assert_eq!(removed, 1);
+        assert!(!world.contains(arrived));
+        assert!(world.contains(walking));
+        // only units leak
+        let tower = tower(&mut world, 10.0, 10.0, 1);
+        assert_eq!(system_remove_arrived(&mut world, &map, &mut events), 0);
+        assert!(world.contains(tower));
+        let leaked: Vec<Event> = events.drain().map(|(_, event)| event).collect();
+        assert!(matches!(leaked.as_slice(), [Event::UnitLeaked { unit }] if *unit == arrived));
+    }
+
+    #[test]
+    fn towers_fire_at_the_closest_unit_in_range() {
+        let mut world = World::new();
+        let mut events = Events::default();
+        let tower = tower(&mut world, 0.0, 0.0, 4);
+        let far = unit(&mut world, 0.0, 8.0, 10);
+        let close = unit(&mut world, 0.0, 5.0, 10);
+        unit(&mut world, 0.0, 20.0, 10);
+        system_fire_at_closest(&mut world, &mut events);
+        assert_eq!(world.get::<Health>(close).unwrap().value, 6);
+        assert_eq!(world.get::<Health>(far).unwrap().value, 10);
+        assert_eq!(world.get::<Target>(tower).unwrap().entity, Some(close));
+        let fired: Vec<Event> = events.drain().map(|(_, event)| event).collect();
+        assert!(matches!(
+            fired.as_slice(),
+            [Event::TowerFired { target, damage: 4, .. }] if *target == close
+        ));
+    }
+
+    #[test]
+    fn killing_shots_score_and_report_the_kill() {
+        let mut world = World::new();
+        let mut events = Events::default();
+        let tower = tower(&mut world, 0.0, 0.0, 4);
+        let unit = unit(&mut world, 3.0, 0.0, 4);
+        system_fire_at_closest(&mut world, &mut events);
+        assert_eq!(world.get::<Score>(tower).unwrap().0, 1);
+        let killed: Vec<Event> = events.drain().map(|(_, event)| event).collect();
+        assert!(matches!(
+            killed.as_slice(),
+            [Event::TowerFired { .. }, Event::UnitKilled { unit: killed, source, .. }]
+                if *killed == unit && *source == DamageSource::Tower(tower)
+        ));
+        assert_eq!(system_remove_dead(&mut world), 1);
+        assert_eq!(system_units_left(&world), 0);
+    }
+
+    #[test]
+    fn towers_skip_dead_units_and_drop_dead_targets() {
+        let mut world = World::new();
+        let mut events = Events::default();
+        let tower = tower(&mut world, 0.0, 0.0, 4);
+        let dead = unit(&mut world, 0.0, 1.0, 4);
+        let alive = unit(&mut world, 0.0, 5.0, 4);
+        world.get_mut::<Health>(dead).unwrap().value = 0;
+        system_fire_at_closest(&mut world, &mut events);
+        assert_eq!(world.get::<Health>(alive).unwrap().value, 0);
+        assert_eq!(world.get::<Target>(tower).unwrap().entity, Some(alive));
+        // the killed target is not kept, even though it is still the closest
+        system_fire_at_closest(&mut world, &mut events);
+        let target = world.get::<Target>(tower).unwrap();
+        assert_eq!(target.entity, None);
+        assert!(target.position.is_none());
+        assert_eq!(world.get::<CombatStats>(tower).unwrap().shots_fired, 1);
+    }
+
+    #[test]
+    fn combat_stats_split_damage_dealt_and_overkill() {
+        let mut world = World::new();
+        let mut events = Events::default();
+        let tower = tower(&mut world, 0.0, 0.0, 4);
+        unit(&mut world, 3.0, 0.0, 6);
+        system_fire_at_closest(&mut world, &mut events);
+        system_fire_at_closest(&mut world, &mut events);
+        let stats = world.get::<CombatStats>(tower).unwrap();
+        assert_eq!(stats.shots_fired, 2);
+        assert_eq!(stats.steps_with_target, 2);
+        assert_eq!(stats.damage_dealt, 6);
+        assert_eq!(stats.overkill, 2);
+        assert_eq!(stats.kills, 1);
+        drop(stats);
+        // steps without a unit in range do not count
+        system_fire_at_closest(&mut world, &mut events);
+        assert_eq!(
+            world.get::<CombatStats>(tower).unwrap().steps_with_target,
+            2
+        );
+    }
+
+    #[test]
+    fn apply_damage_splits_dealt_and_overkill_and_skips_dead_units() {
+        let mut world = World::new();
+        let mut events = Events::default();
+        let unit = unit(&mut world, 0.0, 0.0, 5);
+        let source = DamageSource::Ability(Ability::Meteor);
+        let hit = apply_damage(&world, unit, 3, source, &mut events).unwrap();
+        assert_eq!((hit.dealt, hit.overkill, hit.killed), (3, 0, false));
+        let hit = apply_damage(&world, unit, 7, source, &mut events).unwrap();
+        assert_eq!((hit.dealt, hit.overkill, hit.killed), (2, 5, true));
+        assert!(apply_damage(&world, unit, 1, source, &mut events).is_none());
+        assert!(apply_damage(&world, Entity::DANGLING, 1, source, &mut events).is_none());
+        let hits: Vec<Event> = events.drain().map(|(_, event)| event).collect();
+        assert!(matches!(
+            hits.as_slice(),
+            [
+                Event::AbilityHit { damage: 3, .. },
+                Event::AbilityHit { damage: 7, .. },
+                Event::UnitKilled { .. },
+            ]
+        ));
+    }
+
+    #[test]
+    fn stuns_wear_off() {
+        let mut world = World::new();
+        let unit = world.spawn((Stunned { steps: 2 },));
+        system_update_stuns(&mut world);
+        assert_eq!(world.get::<Stunned>(unit).unwrap().steps, 1);
+        system_update_stuns(&mut world);
+        assert!(world.get::<Stunned>(unit).is_err());
+    }
+
+    #[test]
+    fn strongest_and_weakest_targeting_pick_by_health() {
+        let mut world = World::new();
+        let mut events = Events::default();
+        let tower = tower(&mut world, 0.0, 0.0, 1);
+        let weak = unit(&mut world, 0.0, 5.0, 3);
+        let strong = unit(&mut world, 0.0, 9.0, 30);
+        *world.get_mut::<Targeting>(tower).unwrap() = Targeting::Strongest;
+        system_fire_at_closest(&mut world, &mut events);
+        assert_eq!(world.get::<Target>(tower).unwrap().entity, Some(strong));
+        *world.get_mut::<Targeting>(tower).unwrap() = Targeting::Weakest;
+        system_fire_at_closest(&mut world, &mut events);
+        assert_eq!(world.get::<Target>(tower).unwrap().entity, Some(weak));
+    }
+
+    #[test]
+    fn closest_targeting_keeps_its_target_while_in_range() {
+        let mut world = World::new();
+        let mut events = Events::default();
+        let tower = tower(&mut world, 0.0, 0.0, 1);
+        let first = unit(&mut world, 0.0, 5.0, 30);
+        system_fire_at_closest(&mut world, &mut events);
+        let closer = unit(&mut world, 0.0, 1.0, 30);
+        system_fire_at_closest(&mut world, &mut events);
+        assert_eq!(world.get::<Health>(first).unwrap().value, 28);
+        assert_eq!(world.get::<Health>(closer).unwrap().value, 30);
+        assert_eq!(world.get::<Target>(tower).unwrap().entity, Some(first));
+    }
+
+    #[test]
+    fn first_and_last_targeting_pick_by_path_distance_left() {
+        let mut world = World::new();
+        let mut events = Events::default();
+        let tower = tower(&mut world, 0.0, 0.0, 1);
+        let ahead = unit(&mut world, 0.0, 5.0, 30);
+        let behind = unit(&mut world, 0.0, 1.0, 30);
+        for (unit, remaining) in [(ahead, 10.0), (behind, 50.0)] {
+            let progress = PathProgress {
+                traveled: 0.0,
+                remaining,
+            };
+            world.insert_one(unit, progress).unwrap();
+        }
+        // without progress a unit counts as the farthest from the end
+        let lost = unit(&mut world, 0.0, 9.0, 30);
+        *world.get_mut::<Targeting>(tower).unwrap() = Targeting::First;
+        system_fire_at_closest(&mut world, &mut events);
+        assert_eq!(world.get::<Target>(tower).unwrap().entity, Some(ahead));
+        *world.get_mut::<Targeting>(tower).unwrap() = Targeting::Last;
+        system_fire_at_closest(&mut world, &mut events);
+        assert_eq!(world.get::<Target>(tower).unwrap().entity, Some(lost));
+        world.despawn(lost).unwrap();
+        system_fire_at_closest(&mut world, &mut events);
+        assert_eq!(world.get::<Target>(tower).unwrap().entity, Some(behind));
+    }
+
+    #[test]
+    fn towers_only_target_the_movements_their_kind_can_hit() {
+        let mut world = World::new();
+        let mut events = Events::default();
+        let tower = tower(&mut world, 0.0, 0.0, 1);
+        let walking = unit(&mut world, 0.0, 1.0, 30);
+        let flying = unit(&mut world, 0.0, 5.0, 30);
+        world.insert_one(flying, Movement::Flying).unwrap();
+        *world.get_mut::<TowerKind>(tower).unwrap() = TowerKind::AntiAir;
+        system_fire_at_closest(&mut world, &mut events);
+        assert_eq!(world.get::<Target>(tower).unwrap().entity, Some(flying));
+        *world.get_mut::<TowerKind>(tower).unwrap() = TowerKind::Cannon;
+        world.get_mut::<Target>(tower).unwrap().entity = None;
+        system_fire_at_closest(&mut world, &mut events);
+        assert_eq!(world.get::<Target>(tower).unwrap().entity, Some(walking));
+        assert!(TowerKind::Laser.can_target(Movement::Flying));
+        assert!(TowerKind::Sniper.can_target(Movement::Ground));
+    }
+
+    #[test]
+    fn siege_units_attack_the_closest_tower_until_it_is_destroyed() {
+        let mut world = World::new();
+        let mut events = Events::default();
+        let health = |value| Health { value, max: 10 };
+        let close = world.spawn((
+            Tower,
+            Position { x: 0.0, y: 3.0 },
+            health(5),
+            TowerKind::Laser,
+        ));
+        let far = world.spawn((
+            Tower,
+            Position { x: 0.0, y: 6.0 },
+            health(5),
+            TowerKind::Laser,
+        ));
+        let target = Target {
+            position: None,
+            entity: None,
+        };
+        let siege = world.spawn((
+            Unit,
+            Position { x: 0.0, y: 0.0 },
+            Damage(3),
+            Range { squared: 49.0 },
+            target,
+        ));
+        system_units_attack_towers(&mut world, &mut events);
+        assert_eq!(world.get::<Health>(close).unwrap().value, 2);
+        assert_eq!(world.get::<Target>(siege).unwrap().entity, Some(close));
+        system_units_attack_towers(&mut world, &mut events);
+        let hits: Vec<Event> = events.drain().map(|(_, event)| event).collect();
+        assert!(matches!(
+            hits.as_slice(),
+            [
+                Event::TowerHit { damage: 3, .. },
+                Event::TowerHit { .. },
+                Event::TowerDestroyed { tower, unit, .. },
+            ] if *tower == close && *unit == siege
+        ));
+
+        // destroyed towers leave a ruin behind
+        assert_eq!(system_remove_dead(&mut world), 1);
+        let ruins: Vec<Position> = world
+            .query::<(&Position, &Ruin)>()
+            .iter()
+            .map(|(_, (position, _))| position.clone())
+            .collect();
+        assert_eq!(ruins, vec![Position { x: 0.0, y: 3.0 }]);
+        system_units_attack_towers(&mut world, &mut events);
+        assert_eq!(world.get::<Target>(siege).unwrap().entity, Some(far));
+
+        // out of range towers are left alone and the unit moves on
+        world.get_mut::<Position>(siege).unwrap().y = -10.0;
+        system_units_attack_towers(&mut world, &mut events);
+        assert_eq!(world.get::<Target>(siege).unwrap().entity, None);
+        assert_eq!(world.get::<Health>(far).unwrap().value, 2);
+    }
+
+    #[test]
+    fn siege_units_attack_heroes() {
+        let mut world = World::new();
+        let mut events = Events::default();
+        let hero = world.spawn((
+            Hero,
+            Position { x: 0.0, y: 3.0 },
+            Health { value: 3, max: 3 },
+        ));
+        // units are not attacked
+        unit(&mut world, 0.0, 1.0, 10);
+        let target = Target {
+            position: None,
+            entity: None,
+        };
+        let siege = world.spawn((
+            Unit,
+            Position { x: 0.0, y: 0.0 },
+            Damage(3),
+            Range { squared: 49.0 },
+            target,
+        ));
+        system_units_attack_towers(&mut world, &mut events);
+        assert_eq!(world.get::<Target>(siege).unwrap().entity, Some(hero));
+        let hits: Vec<Event> = events.drain().map(|(_, event)| event).collect();
+        assert!(matches!(
+            hits.as_slice(),
+            [
+                Event::HeroHit { damage: 3, .. },
+                Event::HeroKilled { hero: killed, unit, .. },
+            ] if *killed == hero && *unit == siege
+        ));
+    }
+
+    #[test]
+    fn heroes_walk_to_their_destination_and_fight_the_closest_unit() {
+        let mut world = World::new();
+        let mut events = Events::default();
+        let target = Target {
+            position: None,
+            entity: None,
+        };
+        let hero = world.spawn((
+            Hero,
+            Position { x: 0.0, y: 0.0 },
+            Health { value: 10, max: 10 },
+            Speed(4.0),
+            Damage(2),
+            Range { squared: 100.0 },
+            target,
+            Destination(Position { x: 6.0, y: 0.0 }),
+        ));
+        system_move_heroes(&mut world);
+        assert_eq!(world.get::<Position>(hero).unwrap().x, 4.0);
+        system_move_heroes(&mut world);
+        assert_eq!(world.get::<Position>(hero).unwrap().x, 6.0);
+        assert!(world.get::<Destination>(hero).is_err());
+
+        let far = unit(&mut world, 14.0, 0.0, 10);
+        let close = unit(&mut world, 9.0, 0.0, 2);
+        system_hero_attack(&mut world, &mut events);
+        assert_eq!(world.get::<Target>(hero).unwrap().entity, Some(close));
+        let kills: Vec<Event> = events.drain().map(|(_, event)| event).collect();
+        assert!(matches!(
+            kills.as_slice(),
+            [Event::HeroFired { .. }, Event::UnitKilled { unit, source, .. }]
+                if *unit == close && *source == DamageSource::Hero(hero)
+        ));
+        system_remove_dead(&mut world);
+        system_hero_attack(&mut world, &mut events);
+        assert_eq!(world.get::<Target>(hero).unwrap().entity, Some(far));
+    }
+
+    #[test]
+    fn siege_units_stand_still_while_attacking() {
+        let mut world = World::new();
+        let map = lane(vec![node(0.0, 0.0, Some(1)), node(0.0, 10.0, None)]);
+        let target = Target {
+            position: None,
+            entity: Some(world.spawn(())),
+        };
+        let unit = world.spawn((
+            Unit,
+            Position { x: 0.0, y: 0.0 },
+            Waypoint { node: 1 },
+            Speed(1.0),
+            PathProgress {
+                traveled: 0.0,
+                remaining: 10.0,
+            },
+            target,
+        ));
+        let mut query = MotionQuery::default();
+        system_integrate_motion(&mut world, &mut query, &map);
+        assert_eq!(world.get::<Position>(unit).unwrap().y, 0.0);
+        world.get_mut::<Target>(unit).unwrap().entity = None;
+        system_integrate_motion(&mut world, &mut query, &map);
+        assert_eq!(world.get::<Position>(unit).unwrap().y, 1.0);
+    }
+
+    #[test]
+    fn units_close_to_their_waypoint_head_to_the_next_node() {
+        let mut world = World::new();
+        let map = lane(vec![
+            node(0.0, 0.0, Some(1)),
+            node(0.0, 10.0, Some(2)),
+            node(0.0, 20.0, None),
+        ]);
+        let unit = world.spawn((
+            Unit,
+            Position { x: 0.5, y: 10.0 },
+            Waypoint { node: 1 },
+            Speed(0.1),
+            PathProgress {
+                traveled: 0.0,
+                remaining: 0.0,
+            },
+        ));
+        let mut query = MotionQuery::default();
+        system_integrate_motion(&mut world, &mut query, &map);
+        assert_eq!(world.get::<Waypoint>(unit).unwrap().node, 2);
+        // the unit is not pulled back onto the waypoint it skipped
+        let position = world.get::<Position>(unit).unwrap();
+        assert_eq!(
+            position.x,
+            0.5 - 0.5 * 0.1 / (0.5f32.powi(2) + 100.0).sqrt()
+        );
+        assert!(position.y > 10.0);
+    }
+
+    #[test]
+    fn crowded_units_are_pushed_apart() {
+        let mut world = World::new();
+        let left = world.spawn((Unit, Position { x: 0.0, y: 0.0 }));
+        let right = world.spawn((Unit, Position { x: 4.0, y: 0.0 }));
+        let alone = world.spawn((Unit, Position { x: 100.0, y: 0.0 }));
+        // only units are separated
+        let tower = world.spawn((Tower, Position { x: 2.0, y: 0.0 }));
+        system_separate_units(&mut world);
+        assert_eq!(
+            *world.get::<Position>(left).unwrap(),
+            Position { x: -1.0, y: 0.0 }
+        );
+        assert_eq!(
+            *world.get::<Position>(right).unwrap(),
+            Position { x: 5.0, y: 0.0 }
+        );
+        assert_eq!(
+            *world.get::<Position>(alone).unwrap(),
+            Position { x: 100.0, y: 0.0 }
+        );
+        assert_eq!(
+            *world.get::<Position>(tower).unwrap(),
+            Position { x: 2.0, y: 0.0 }
+        );
+    }
+
+    #[test]
+    fn coincident_units_split_in_opposite_directions() {
+        let mut world = World::new();
+        let a = world.spawn((Unit, Position { x: 0.0, y: 0.0 }));
+        let b = world.spawn((Unit, Position { x: 0.0, y: 0.0 }));
+        system_separate_units(&mut world);
+        let a = Position::clone(&world.get::<Position>(a).unwrap());
+        let b = Position::clone(&world.get::<Position>(b).unwrap());
+        assert!(distance(&a, &b) > 0.0);
+        assert!((a.x + b.x).abs() < 1e-6 && (a.y + b.y).abs() < 1e-6);
+    }
+
+    #[test]
+    fn separation_is_capped_per_step() {
+        let mut world = World::new();
+        let left = world.spawn((Unit, Position { x: 0.0, y: 0.0 }));
+        world.spawn((Unit, Position { x: 0.5, y: 0.0 }));
+        system_separate_units(&mut world);
+        assert_eq!(world.get::<Position>(left).unwrap().x, -MAX_SEPARATION_STEP);
+    }
+
+    #[test]
+    fn movement_left_at_a_node_carries_over_to_the_next() {
+        let mut world = World::new();
+        let map = lane(vec![
+            node(0.0, 0.0, Some(1)),
+            node(3.0, 0.0, Some(2)),
+            node(3.0, 10.0, None),
+        ]);
+        let progress = PathProgress {
+            traveled: 0.0,
+            remaining: map.shortest_remaining_distance(&Position { x: 0.0, y: 0.0 }, 1),
+        };
+        assert_eq!(progress.remaining, 13.0);
+        assert_eq!(progress.eta(&Speed(5.0)), Some(3));
+        let unit = world.spawn((
+            Unit,
+            Position { x: 0.0, y: 0.0 },
+            Waypoint { node: 1 },
+            Speed(5.0),
+            progress,
+        ));
+        let mut query = MotionQuery::default();
+        system_integrate_motion(&mut world, &mut query, &map);
+        assert_eq!(
+            *world.get::<Position>(unit).unwrap(),
+            Position { x: 3.0, y: 2.0 }
+        );
+        assert_eq!(world.get::<Waypoint>(unit).unwrap().node, 2);
+        let progress = PathProgress::clone(&world.get::<PathProgress>(unit).unwrap());
+        assert_eq!((progress.traveled, progress.remaining), (5.0, 8.0));
+
+        // goals stop the unit
+        for _ in 0..3 {
+            system_integrate_motion(&mut world, &mut query, &map);
+        }
+        assert_eq!(
+            *world.get::<Position>(unit).unwrap(),
+            Position { x: 3.0, y: 10.0 }
+        );
+        assert_eq!(world.get::<Waypoint>(unit).unwrap().node, 2);
+        let progress = PathProgress::clone(&world.get::<PathProgress>(unit).unwrap());
+        assert_eq!((progress.traveled, progress.remaining), (13.0, 0.0));
+    }
+}
@@ -0,0 +1,311 @@
+use crate::abilities::{Abilities, Ability};
+use crate::actions::{Action, Mode};
+use crate::components::{EntityKind, Movement, TowerKind};
+use crate::spawns::{rebuild_cost, refund, repair_cost, upgrade_cost, Selection, SelectionStatus};
+use macroquad::prelude::{screen_height, screen_width, vec2};
+use macroquad::ui::{hash, root_ui, widgets, Ui};
+
+pub const TOP_BAR_HEIGHT: f32 = 32.0;
+pub const SIDE_PANEL_WIDTH: f32 = 240.0;
+const MAX_SPEED: usize = 16;
+
+pub struct Hud<'a> {
+    pub gold: i32,
+    pub lives: i32,
+    pub wave: usize,
+    pub steps_per_frame: usize,
+    pub paused: bool,
+    pub game_over: bool,
+    pub mode: &'a Mode,
+    pub shop: TowerKind,
+    pub abilities: &'a Abilities,
+    pub step: usize,
+    pub gold_rush_left: usize,
+    pub hero_level: u32,
+    // steps left before the dead hero respawns
+    pub hero_respawn: Option<usize>,
+}
+
+// Draws the top bar and the side panel, returns the action of the clicked button if any
+pub fn draw_hud(hud: &Hud, selection: &[Selection]) -> Option<Action> {
+    let mut action = None;
+    widgets::Window::new(
+        hash!("top_bar"),
+        vec2(0., 0.),
+        vec2(screen_width(), TOP_BAR_HEIGHT),
+    )
+    .titlebar(false)
+    .movable(false)
+    .ui(&mut root_ui(), |ui| {
+        draw_top_bar(ui, hud, &mut action);
+    });
+    widgets::Window::new(
+        hash!("side_panel"),
+        vec2(screen_width() - SIDE_PANEL_WIDTH, TOP_BAR_HEIGHT),
+        vec2(SIDE_PANEL_WIDTH, screen_height() - TOP_BAR_HEIGHT),
+    )
+    .titlebar(false)
+    .movable(false)
+    .ui(&mut root_ui(), |ui| {
+        draw_shop(ui, hud, &mut action);
+        ui.separator();
+        draw_abilities(ui, hud, &mut action);
+        ui.separator();
+        draw_inspector(ui, selection, &mut action);
+    });
+    action
+}
+
+fn draw_top_bar(ui: &mut Ui, hud: &Hud, action: &mut Option<Action>) {
+    let status = if hud.game_over {
+        " GAME OVER"
+    } else if hud.paused {
+        " paused"
+    } else {
+        ""
+    };
+    let hero = match hud.hero_respawn {
+        Some(steps) => format!("respawns in {}", steps),
+        None => format!("level {}", hud.hero_level),
+    };
+    ui.label(
+        None,
+        &format!(
+            "gold: {}  lives: {}  wave: {}  hero: {}  speed: x{}{}",
+            hud.gold, hud.lives, hud.wave, hero, hud.steps_per_frame, status
+        ),
+    );
+    ui.same_line(0.);
+    if ui.button(None, "-") && hud.steps_per_frame > 1 {
+        *action = Some(Action::SetSpeed(hud.steps_per_frame - 1));
+    }
+    ui.same_line(0.);
+    if ui.button(None, "+") && hud.steps_per_frame < MAX_SPEED {
+        *action = Some(Action::SetSpeed(hud.steps_per_frame + 1));
+    }
+    ui.same_line(0.);
+    if ui.button(None, if hud.paused { "resume" } else { "pause" }) {
+        *action = Some(Action::TogglePause);
+    }
+}
+
+fn draw_shop(ui: &mut Ui, hud: &Hud, action: &mut Option<Action>) {
+    ui.label(None, "Shop");
+    for kind in TowerKind::ALL {
+        let selected = *hud.mode == Mode::Build && hud.shop == kind;
+        if widgets::Button::new(format!("{:?} ({} gold)", kind, kind.cost()).as_str())
+            .selected(selected)
+            .ui(ui)
+        {
+            *action = Some(Action::SelectShop(kind));
+        }
+    }
+    for (mode, label) in [
+        (Mode::View, "View"),
+        (Mode::Remove, "Sell"),
+        (Mode::Hero, "Hero"),
+    ] {
+        if widgets::Button::new(label)
+            .selected(*hud.mode == mode)
+            .ui(ui)
+        {
+            *action = Some(Action::ChangeMode(mode));
+        }
+        ui.same_line(0.);
+    }
+    ui.label(None, "");
+}
+
+fn draw_abilities(ui: &mut Ui, hud: &Hud, action: &mut Option<Action>) {
+    ui.label(None, "Abilities");
+    for ability in Ability::ALL {
+        let cooldown = hud.abilities.cooldown_left(ability, hud.step);
+        let label = if cooldown > 0 {
+            format!("{:?} ({} steps)", ability, cooldown)
+        } else {
+            format!("{:?} ({} gold)", ability, ability.cost())
+        };
+        if widgets::Button::new(label.as_str())
+            .selected(*hud.mode == Mode::Aim(ability))
+            .ui(ui)
+        {
+            *action = Some(match ability.radius() {
+                Some(_) => Action::SelectAbility(ability),
+                None => Action::CastAbility(ability, None),
+            });
+        }
+    }
+    if hud.gold_rush_left > 0 {
+        ui.label(
+            None,
+            &format!("gold rush: {} steps left", hud.gold_rush_left),
+        );
+    }
+}
+
+fn describe(selection: &Selection) -> Vec<String> {
+    let mut description: Vec<String> = vec![];
+    match (&selection.entity_kind, &selection.kind, &selection.ruin) {
+        (Some(EntityKind::Tower), Some(kind), _) => {
+            description.push(format!("{:?} tower", kind));
+        }
+        (Some(EntityKind::Ruin), _, Some(ruin)) => {
+            description.push(format!("ruined {:?} tower", ruin.kind));
+        }
+        (Some(EntityKind::Unit), _, _) => {
+            match &selection.movement {
+                Some(Movement::Flying) => description.push("flying unit".to_string()),
+                _ => description.push("unit".to_string()),
+            }
+            if selection.damage.is_some() {
+                description.push("attacks towers".to_string());
+            }
+        }
+        (Some(EntityKind::Hero), _, _) => description.push("hero".to_string()),
+        _ => description.push("entity".to_string()),
+    }
+    match &selection.status {
+        SelectionStatus::Alive => {}
+        SelectionStatus::Killed { step, source } => {
+            description.push(format!("killed at step {} by {}", step, source));
+        }
+        SelectionStatus::Leaked { step } => {
+            description.push(format!("leaked at step {}", step));
+        }
+        SelectionStatus::Sold { step } => {
+            description.push(format!("sold at step {}", step));
+        }
+        SelectionStatus::Destroyed { step, unit } => {
+            description.push(format!("destroyed at step {} by unit {:?}", step, unit));
+        }
+        SelectionStatus::HeroKilled { step, unit } => {
+            description.push(format!("killed at step {} by unit {:?}", step, unit));
+        }
+        SelectionStatus::Despawned { step } => {
+            description.push(format!("despawned at step {}", step));
+        }
+    }
+    if let Some(position) = &selection.position {
+        description.push(format!("position: {:.0}, {:.0}", position.x, position.y));
+    }
+    if let Some(health) = &selection.health {
+        description.push(format!("health: {}/{}", health.value, health.max));
+    }
+    if let Some(speed) = &selection.speed {
+        description.push(format!("speed: {:.1}", speed.0));
+    }
+    if let Some(progress) = &selection.progress {
+        description.push(format!(
+            "traveled: {:.0}, shortest remaining: {:.0}",
+            progress.traveled, progress.remaining
+        ));
+        if let (SelectionStatus::Alive, Some(eta)) = (
+            &selection.status,
+            selection.speed.as_ref().and_then(|s| progress.eta(s)),
+        ) {
+            description.push(format!("leaks in {} steps at the earliest", eta));
+        }
+    }
+    if let Some(damage) = &selection.damage {
+        description.push(format!("damage: {}", damage.0));
+    }
+    if let Some(range) = &selection.range {
+        description.push(format!("range: {:.0}", range.squared.sqrt()));
+    }
+    if let Some(level) = &selection.level {
+        description.push(format!("level: {}", level.0));
+    }
+    if let Some(score) = &selection.score {
+        description.push(format!("kills: {}", score.0));
+    }
+    description
+}
+
+fn summarize(selection: &[Selection]) -> Vec<String> {
+    let is = |kind: EntityKind| move |s: &&Selection| s.entity_kind == Some(kind);
+    let units: Vec<_> = selection
+        .iter()
+        .filter(is(EntityKind::Unit))
+        .filter_map(|s| s.health.as_ref())
+        .collect();
+    let towers = selection.iter().filter(is(EntityKind::Tower)).count();
+    let damage_per_step: i32 = selection
+        .iter()
+        .filter(is(EntityKind::Tower))
+        .filter_map(|s| s.damage.as_ref())
+        .map(|d| d.0)
+        .sum();
+    let average_health = if units.is_empty() {
+        0.0
+    } else {
+        units.iter().map(|h| h.value as f32).sum::<f32>() / units.len() as f32
+    };
+    vec![
+        format!("selected: {}", selection.len()),
+        format!("units: {}, towers: {}", units.len(), towers),
+        format!("damage/step: {}", damage_per_step),
+        format!("average health: {:.1}", average_health),
+    ]
+}
+
+fn draw_inspector(ui: &mut Ui, selection: &[Selection], action: &mut Option<Action>) {
+    ui.label(None, "Inspector");
+    let lines = match selection {
+        [] => vec!["nothing selected".to_string()],
+        [selected] => describe(selected),
+        _ => summarize(selection),
+    };
+    for line in lines {
+        ui.label(None, &line);
+    }
+
+    let alive = |s: &&Selection| matches!(s.status, SelectionStatus::Alive);
+    let rebuild: i32 = selection
+        .iter()
+        .filter(alive)
+        .filter_map(|s| s.ruin.as_ref())
+        .map(rebuild_cost)
+        .sum();
+    if rebuild > 0 && ui.button(None, format!("Rebuild ({} gold)", rebuild).as_str()) {
+        *action = Some(Action::RebuildSelected);
+    }
+    let towers: Vec<&Selection> = selection
+        .iter()
+        .filter(alive)
+        .filter(|s| s.entity_kind == Some(EntityKind::Tower))
+        .collect();
+    if towers.is_empty() {
+        return;
+    }
+    let repair: i32 = towers
+        .iter()
+        .filter_map(|s| s.health.as_ref())
+        .map(repair_cost)
+        .sum();
+    if repair > 0 && ui.button(None, format!("Repair ({} gold)", repair).as_str()) {
+        *action = Some(Action::RepairSelected);
+    }
+    let upgrade: i32 = towers
+        .iter()
+        .filter_map(|s| match (&s.kind, &s.level) {
+            (Some(kind), Some(level)) => Some(upgrade_cost(*kind, level)),
+            _ => None,
+        })
+        .sum();
+    let sell: i32 = towers
+        .iter()
+        .filter_map(|s| s.value.as_ref())
+        .map(refund)
+        .sum();
+    if ui.button(None, format!("Upgrade ({} gold)", upgrade).as_str()) {
+        *action = Some(Action::UpgradeSelected);
+    }
+    if ui.button(None, format!("Sell (+{} gold)", sell).as_str()) {
+        *action = Some(Action::SellSelected);
+    }
+    if let Some(targeting) = towers.iter().find_map(|s| s.targeting) {
+        if ui.button(None, format!("Targeting: {:?}", targeting).as_str()) {
+            *action = Some(Action::CycleTargeting);
+        }
+    }
+}